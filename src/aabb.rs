@@ -0,0 +1,135 @@
+use crate::{Point3, Vec3};
+
+// Axis-aligned bounding box
+#[derive(Copy, Clone)]
+pub struct Aabb {
+    pub minimum: Point3,
+    pub maximum: Point3,
+}
+
+impl Aabb {
+    pub fn new(a: Point3, b: Point3) -> Self {
+        Aabb {
+            minimum: Vec3::min(a, b),
+            maximum: Vec3::max(a, b),
+        }
+    }
+
+    // Identity for surrounding_box, contains nothing
+    pub const EMPTY: Aabb = Aabb {
+        minimum: Vec3 {
+            x: f64::INFINITY,
+            y: f64::INFINITY,
+            z: f64::INFINITY,
+        },
+        maximum: Vec3 {
+            x: f64::NEG_INFINITY,
+            y: f64::NEG_INFINITY,
+            z: f64::NEG_INFINITY,
+        },
+    };
+
+    pub fn surrounding_box(box0: &Aabb, box1: &Aabb) -> Aabb {
+        Aabb {
+            minimum: Vec3::min(box0.minimum, box1.minimum),
+            maximum: Vec3::max(box0.maximum, box1.maximum),
+        }
+    }
+
+    pub fn surrounding_point(&self, p: Point3) -> Aabb {
+        Aabb {
+            minimum: Vec3::min(self.minimum, p),
+            maximum: Vec3::max(self.maximum, p),
+        }
+    }
+
+    // Grows flat sides to at least delta, planar shapes would otherwise have no volume
+    pub fn padded(&self, delta: f64) -> Aabb {
+        let mut minimum = self.minimum;
+        let mut maximum = self.maximum;
+        for (min, max) in [
+            (&mut minimum.x, &mut maximum.x),
+            (&mut minimum.y, &mut maximum.y),
+            (&mut minimum.z, &mut maximum.z),
+        ] {
+            if *max - *min < delta {
+                *min -= delta / 2.0;
+                *max += delta / 2.0;
+            }
+        }
+        Aabb { minimum, maximum }
+    }
+
+    // Moves every side outwards by margin
+    pub fn expanded(&self, margin: f64) -> Aabb {
+        let margin = Vec3::ONE * margin;
+        Aabb {
+            minimum: self.minimum - margin,
+            maximum: self.maximum + margin,
+        }
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        0.5 * (self.minimum + self.maximum)
+    }
+
+    pub fn diagonal(&self) -> Vec3 {
+        self.maximum - self.minimum
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.diagonal();
+        if d.x < 0.0 || d.y < 0.0 || d.z < 0.0 {
+            return 0.0;
+        }
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        self.diagonal().max_dimension()
+    }
+
+    // Position of p relative to the box corners, 0 at minimum and 1 at maximum
+    pub fn offset(&self, p: Point3) -> Vec3 {
+        let mut o = p - self.minimum;
+        let d = self.diagonal();
+        if d.x > 0.0 {
+            o.x /= d.x;
+        }
+        if d.y > 0.0 {
+            o.y /= d.y;
+        }
+        if d.z > 0.0 {
+            o.z /= d.z;
+        }
+        o
+    }
+
+    // Slab test, the inverse direction is precomputed once per ray during BVH traversal
+    pub fn hit_with_inverse(
+        &self,
+        origin: Point3,
+        inv_direction: Vec3,
+        mut t_min: f64,
+        mut t_max: f64,
+    ) -> bool {
+        for axis in 0..3 {
+            let mut t0 = (self.minimum[axis] - origin[axis]) * inv_direction[axis];
+            let mut t1 = (self.maximum[axis] - origin[axis]) * inv_direction[axis];
+            if inv_direction[axis] < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // NaN from 0 * inf leaves the bounds untouched
+            if t0 > t_min {
+                t_min = t0;
+            }
+            if t1 < t_max {
+                t_max = t1;
+            }
+            if t_max < t_min {
+                return false;
+            }
+        }
+        true
+    }
+}
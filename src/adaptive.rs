@@ -0,0 +1,123 @@
+use crate::Color;
+
+// Below this brightness the error is measured as if the pixel were this bright,
+// otherwise black pixels would never be converged
+const MIN_BRIGHTNESS: f64 = 1e-3;
+
+// Running mean of the samples of one pixel and the variance of their brightness
+// (Welford's algorithm)
+pub struct PixelStats {
+    count: u32,
+    sum: Color,
+    brightness_mean: f64,
+    // sum of squared differences from the mean brightness
+    brightness_m2: f64,
+}
+
+impl Default for PixelStats {
+    fn default() -> Self {
+        PixelStats {
+            count: 0,
+            sum: Color::ZERO,
+            brightness_mean: 0.0,
+            brightness_m2: 0.0,
+        }
+    }
+}
+
+impl PixelStats {
+    pub fn add(&mut self, color: Color) {
+        self.count += 1;
+        self.sum += color;
+
+        let brightness = luminance(color);
+        let delta = brightness - self.brightness_mean;
+        self.brightness_mean += delta / self.count as f64;
+        self.brightness_m2 += delta * (brightness - self.brightness_mean);
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn mean(&self) -> Color {
+        if self.count == 0 {
+            return Color::ZERO;
+        }
+        self.sum / self.count as f64
+    }
+
+    // Estimated standard error of the pixel after gamma correction, in units of the
+    // displayed range. The square root halves relative errors, so dark pixels need
+    // fewer samples than their raw relative error would suggest.
+    pub fn error(&self) -> f64 {
+        if self.count < 2 {
+            return f64::INFINITY;
+        }
+        let variance = self.brightness_m2 / (self.count - 1) as f64;
+        let standard_error = (variance / self.count as f64).sqrt();
+        standard_error / (2.0 * self.brightness_mean.max(MIN_BRIGHTNESS).sqrt())
+    }
+}
+
+// Rec. 709 weights, how bright the color looks
+fn luminance(color: Color) -> f64 {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_two_pass_mean_and_variance() {
+        let colors: Vec<Color> = (0..20)
+            .map(|i| {
+                let i = i as f64;
+                Color::new((i * 0.37).sin().abs(), (i * 0.11) % 1.0, 2.0 - i * 0.05)
+            })
+            .collect();
+        let mut stats = PixelStats::default();
+        for &color in &colors {
+            stats.add(color);
+        }
+
+        let n = colors.len() as f64;
+        let mean = colors.iter().copied().sum::<Color>() / n;
+        assert_eq!(stats.count(), colors.len() as u32);
+        assert!((stats.mean() - mean).length() < 1e-12);
+
+        let brightness: Vec<f64> = colors.iter().map(|&color| luminance(color)).collect();
+        let brightness_mean = brightness.iter().sum::<f64>() / n;
+        let variance = brightness
+            .iter()
+            .map(|b| (b - brightness_mean).powi(2))
+            .sum::<f64>()
+            / (n - 1.0);
+        let expected = (variance / n).sqrt() / (2.0 * brightness_mean.sqrt());
+        assert!(
+            (stats.error() - expected).abs() < 1e-12,
+            "{}",
+            stats.error()
+        );
+    }
+
+    #[test]
+    fn constant_samples_converge() {
+        let mut stats = PixelStats::default();
+        assert_eq!(stats.error(), f64::INFINITY);
+        stats.add(Color::new(0.2, 0.4, 0.6));
+        assert_eq!(stats.error(), f64::INFINITY);
+        for _ in 0..9 {
+            stats.add(Color::new(0.2, 0.4, 0.6));
+        }
+        assert!(stats.error().abs() < 1e-12, "{}", stats.error());
+
+        // black pixels converge too
+        let mut black = PixelStats::default();
+        for _ in 0..4 {
+            black.add(Color::ZERO);
+        }
+        assert_eq!(black.error(), 0.0);
+    }
+}
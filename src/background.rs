@@ -0,0 +1,32 @@
+use crate::{Color, Ray};
+
+// Radiance arriving from directions where a ray hits nothing
+pub enum Background {
+    Solid(Color),
+    // blends from the horizon color to the zenith color with the ray height
+    Gradient { horizon: Color, zenith: Color },
+}
+
+impl Background {
+    pub const BLACK: Background = Background::Solid(Color::ZERO);
+
+    pub const SKY: Background = Background::Gradient {
+        horizon: Color::ONE,
+        zenith: Color {
+            x: 0.5,
+            y: 0.7,
+            z: 1.0,
+        },
+    };
+
+    pub fn color(&self, r: &Ray) -> Color {
+        match self {
+            Background::Solid(color) => *color,
+            Background::Gradient { horizon, zenith } => {
+                let unit_direction = r.direction().unit_vector();
+                let t = 0.5 * (unit_direction.y + 1.0);
+                (1.0 - t) * *horizon + t * *zenith
+            }
+        }
+    }
+}
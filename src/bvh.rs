@@ -0,0 +1,446 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::{Point3, Ray, Vec3};
+
+// Number of centroid buckets evaluated by the surface area heuristic
+const BUCKET_COUNT: usize = 12;
+const MAX_PRIMITIVES_IN_LEAF: usize = 4;
+// Cost of visiting an interior node relative to one primitive intersection
+const TRAVERSAL_COST: f64 = 0.125;
+// Deeper than this the builder switches to median splits, which keeps the
+// tree shallow enough for the fixed size traversal stack
+const MAX_SAH_DEPTH: usize = 32;
+const STACK_SIZE: usize = 64;
+
+#[derive(Copy, Clone)]
+struct PrimitiveInfo {
+    index: usize,
+    bounds: Aabb,
+    centroid: Point3,
+}
+
+// Nodes are stored depth first, so the first child of an interior node
+// directly follows its parent and only the second child needs an offset
+#[derive(Copy, Clone)]
+struct LinearBvhNode {
+    bounds: Aabb,
+    // leaf: index of the first primitive, interior: index of the second child
+    offset: usize,
+    // zero for interior nodes
+    primitive_count: usize,
+    axis: usize,
+}
+
+#[derive(Copy, Clone, Default)]
+struct Bucket {
+    count: usize,
+    bounds: Option<Aabb>,
+}
+
+pub struct Bvh<T: Hittable> {
+    primitives: Vec<T>,
+    nodes: Vec<LinearBvhNode>,
+    // primitives without a bounding box are tested against every ray
+    unbounded: Vec<T>,
+}
+
+impl<T: Hittable> Bvh<T> {
+    pub fn new(objects: Vec<T>) -> Self {
+        let mut bounded = Vec::with_capacity(objects.len());
+        let mut unbounded = Vec::new();
+        let mut infos = Vec::with_capacity(objects.len());
+
+        for object in objects {
+            match object.bounding_box() {
+                Some(bounds) => {
+                    infos.push(PrimitiveInfo {
+                        index: bounded.len(),
+                        bounds,
+                        centroid: bounds.centroid(),
+                    });
+                    bounded.push(Some(object));
+                }
+                None => unbounded.push(object),
+            }
+        }
+
+        let mut nodes = Vec::with_capacity(2 * infos.len());
+        let mut ordered = Vec::with_capacity(infos.len());
+        if !infos.is_empty() {
+            Bvh::<T>::build_recursive(&mut nodes, &mut infos, &mut ordered, 0);
+        }
+
+        let primitives = ordered
+            .into_iter()
+            .map(|index| bounded[index].take().unwrap())
+            .collect();
+
+        Bvh {
+            primitives,
+            nodes,
+            unbounded,
+        }
+    }
+
+    pub fn objects(&self) -> impl Iterator<Item = &T> {
+        self.primitives.iter().chain(self.unbounded.iter())
+    }
+
+    fn build_recursive(
+        nodes: &mut Vec<LinearBvhNode>,
+        infos: &mut [PrimitiveInfo],
+        ordered: &mut Vec<usize>,
+        depth: usize,
+    ) -> usize {
+        let node_index = nodes.len();
+        let bounds = infos.iter().fold(Aabb::EMPTY, |b, info| {
+            Aabb::surrounding_box(&b, &info.bounds)
+        });
+        nodes.push(LinearBvhNode {
+            bounds,
+            offset: 0,
+            primitive_count: 0,
+            axis: 0,
+        });
+
+        let count = infos.len();
+        let centroid_bounds = infos
+            .iter()
+            .fold(Aabb::EMPTY, |b, info| b.surrounding_point(info.centroid));
+        let axis = centroid_bounds.longest_axis();
+
+        // All centroids coincide, there is nothing to split on
+        if count == 1 || centroid_bounds.maximum[axis] <= centroid_bounds.minimum[axis] {
+            Bvh::<T>::make_leaf(&mut nodes[node_index], infos, ordered);
+            return node_index;
+        }
+
+        let mid = if count <= 2 || depth >= MAX_SAH_DEPTH {
+            let mid = count / 2;
+            infos.select_nth_unstable_by(mid, |a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+            mid
+        } else {
+            let bucket_index = |centroid: Point3| {
+                let b = (BUCKET_COUNT as f64 * centroid_bounds.offset(centroid)[axis]) as usize;
+                b.min(BUCKET_COUNT - 1)
+            };
+
+            let mut buckets = [Bucket::default(); BUCKET_COUNT];
+            for info in infos.iter() {
+                let bucket = &mut buckets[bucket_index(info.centroid)];
+                bucket.count += 1;
+                bucket.bounds = Some(match bucket.bounds {
+                    Some(b) => Aabb::surrounding_box(&b, &info.bounds),
+                    None => info.bounds,
+                });
+            }
+
+            // Sweep from both ends to get the cost of splitting after each bucket
+            let mut costs = [0.0; BUCKET_COUNT - 1];
+            let mut below = Aabb::EMPTY;
+            let mut below_count = 0;
+            for (i, cost) in costs.iter_mut().enumerate() {
+                if let Some(b) = buckets[i].bounds {
+                    below = Aabb::surrounding_box(&below, &b);
+                }
+                below_count += buckets[i].count;
+                *cost = below_count as f64 * below.surface_area();
+            }
+            let mut above = Aabb::EMPTY;
+            let mut above_count = 0;
+            for i in (1..BUCKET_COUNT).rev() {
+                if let Some(b) = buckets[i].bounds {
+                    above = Aabb::surrounding_box(&above, &b);
+                }
+                above_count += buckets[i].count;
+                costs[i - 1] += above_count as f64 * above.surface_area();
+            }
+
+            let (split_bucket, min_cost) = costs
+                .iter()
+                .enumerate()
+                .min_by(|a, b| a.1.total_cmp(b.1))
+                .map(|(i, cost)| (i, TRAVERSAL_COST + cost / bounds.surface_area()))
+                .unwrap();
+
+            let leaf_cost = count as f64;
+            if count <= MAX_PRIMITIVES_IN_LEAF && min_cost >= leaf_cost {
+                Bvh::<T>::make_leaf(&mut nodes[node_index], infos, ordered);
+                return node_index;
+            }
+
+            partition(infos, |info| bucket_index(info.centroid) <= split_bucket)
+        };
+
+        Bvh::<T>::build_recursive(nodes, &mut infos[..mid], ordered, depth + 1);
+        let second_child = Bvh::<T>::build_recursive(nodes, &mut infos[mid..], ordered, depth + 1);
+
+        let node = &mut nodes[node_index];
+        node.offset = second_child;
+        node.axis = axis;
+        node_index
+    }
+
+    fn make_leaf(node: &mut LinearBvhNode, infos: &[PrimitiveInfo], ordered: &mut Vec<usize>) {
+        node.offset = ordered.len();
+        node.primitive_count = infos.len();
+        ordered.extend(infos.iter().map(|info| info.index));
+    }
+}
+
+// Moves the elements matching the predicate to the front, returns their count
+fn partition<F>(infos: &mut [PrimitiveInfo], predicate: F) -> usize
+where
+    F: Fn(&PrimitiveInfo) -> bool,
+{
+    let mut first = 0;
+    for i in 0..infos.len() {
+        if predicate(&infos[i]) {
+            infos.swap(first, i);
+            first += 1;
+        }
+    }
+    first
+}
+
+impl<T: Hittable> Hittable for Bvh<T> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut hit_anything = None;
+        let mut closest_so_far = t_max;
+
+        for object in self.unbounded.as_slice() {
+            if let Some(rec) = object.hit(r, t_min, closest_so_far) {
+                hit_anything = Some(rec);
+                closest_so_far = rec.t;
+            }
+        }
+
+        if self.nodes.is_empty() {
+            return hit_anything;
+        }
+
+        let origin = r.origin();
+        let direction = r.direction();
+        let inv_direction = Vec3::new(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z);
+        let direction_is_negative = [
+            inv_direction.x < 0.0,
+            inv_direction.y < 0.0,
+            inv_direction.z < 0.0,
+        ];
+
+        let mut stack = [0; STACK_SIZE];
+        let mut to_visit = 0;
+        let mut current = 0;
+
+        loop {
+            let node = &self.nodes[current];
+            if node
+                .bounds
+                .hit_with_inverse(origin, inv_direction, t_min, closest_so_far)
+            {
+                if node.primitive_count > 0 {
+                    let leaf = &self.primitives[node.offset..node.offset + node.primitive_count];
+                    for object in leaf {
+                        if let Some(rec) = object.hit(r, t_min, closest_so_far) {
+                            hit_anything = Some(rec);
+                            closest_so_far = rec.t;
+                        }
+                    }
+                } else {
+                    // Visit the child closer to the ray origin first
+                    if direction_is_negative[node.axis] {
+                        stack[to_visit] = current + 1;
+                        current = node.offset;
+                    } else {
+                        stack[to_visit] = node.offset;
+                        current += 1;
+                    }
+                    to_visit += 1;
+                    continue;
+                }
+            }
+
+            if to_visit == 0 {
+                break;
+            }
+            to_visit -= 1;
+            current = stack[to_visit];
+        }
+
+        hit_anything
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if !self.unbounded.is_empty() || self.nodes.is_empty() {
+            return None;
+        }
+        Some(self.nodes[0].bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittables;
+    use crate::hittable_list::HittableList;
+    use crate::material::{EmptyMaterial, Materials};
+    use crate::rng::Pcg32;
+    use crate::sphere::Sphere;
+
+    fn sphere(center: Point3, radius: f64) -> Sphere {
+        Sphere::new(center, radius, Materials::EmptyMaterial(EmptyMaterial {}))
+    }
+
+    fn random_point(rng: &mut Pcg32, size: f64) -> Point3 {
+        let mut coordinate = || size * (2.0 * rng.next_double() - 1.0);
+        Point3::new(coordinate(), coordinate(), coordinate())
+    }
+
+    // Rays from all around towards points near the given ones
+    fn rays(targets: &[Point3]) -> Vec<Ray> {
+        let mut rng = Pcg32::new(7, 0);
+        (0..2000)
+            .map(|_| {
+                let target = targets[rng.next_index(targets.len())] + random_point(&mut rng, 1.0);
+                let origin = target + random_point(&mut rng, 30.0);
+                Ray::new(origin, target - origin, 0.0)
+            })
+            .collect()
+    }
+
+    // Levels of the subtree below the node
+    fn depth(bvh: &Bvh<Hittables>, node: usize) -> usize {
+        let LinearBvhNode {
+            offset,
+            primitive_count,
+            ..
+        } = bvh.nodes[node];
+        if primitive_count > 0 {
+            return 1;
+        }
+        1 + depth(bvh, node + 1).max(depth(bvh, offset))
+    }
+
+    // Every ray finds the same closest hit in the BVH as in a plain list of spheres
+    // with these centers and radii
+    fn assert_same_hits(spheres: &[(Point3, f64)]) -> Bvh<Hittables> {
+        let mut list = HittableList::EMPTY;
+        let mut objects = Vec::new();
+        for &(center, radius) in spheres {
+            objects.push(Hittables::from(sphere(center, radius)));
+            list.add(sphere(center, radius));
+        }
+        let bvh = Bvh::new(objects);
+
+        let centers = spheres
+            .iter()
+            .map(|&(center, _)| center)
+            .collect::<Vec<_>>();
+        let mut hits = 0;
+        for ray in rays(&centers) {
+            let expected = list.hit(&ray, 0.001, f64::INFINITY).map(|rec| rec.t);
+            let found = bvh.hit(&ray, 0.001, f64::INFINITY).map(|rec| rec.t);
+            assert_eq!(found, expected);
+            hits += expected.is_some() as usize;
+        }
+        assert!(hits > 100, "only {hits} rays hit anything");
+        bvh
+    }
+
+    #[test]
+    fn random_spheres() {
+        let mut rng = Pcg32::new(1, 0);
+        let spheres = (0..500)
+            .map(|_| (random_point(&mut rng, 10.0), 0.2 + rng.next_double()))
+            .collect::<Vec<_>>();
+        assert_same_hits(&spheres);
+    }
+
+    #[test]
+    fn coincident_centroids() {
+        // Concentric spheres can't be split, they end up in one large leaf
+        let mut spheres = (1..=10)
+            .map(|i| (Point3::new(1.0, 2.0, 3.0), i as f64 * 0.5))
+            .collect::<Vec<_>>();
+        let mut rng = Pcg32::new(2, 0);
+        spheres.extend((0..20).map(|_| (random_point(&mut rng, 10.0), 0.5)));
+        let bvh = assert_same_hits(&spheres);
+        assert!(bvh.nodes.iter().any(|node| node.primitive_count == 10));
+    }
+
+    #[test]
+    fn deep_tree_falls_back_to_median_splits() {
+        // Each sphere is twice the size of the previous one, splitting off the largest
+        // is always cheapest and the tree becomes a chain deeper than MAX_SAH_DEPTH
+        let spheres = (0..100)
+            .map(|i| (Point3::new(2f64.powi(i), 0.0, 0.0), 0.4 * 2f64.powi(i)))
+            .collect::<Vec<_>>();
+        let bvh = assert_same_hits(&spheres);
+        assert!(depth(&bvh, 0) > MAX_SAH_DEPTH);
+    }
+
+    // Infinite floor at y = 0
+    struct Floor;
+
+    impl Hittable for Floor {
+        fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+            let t = -r.origin().y / r.direction().y;
+            (t_min..t_max)
+                .contains(&t)
+                .then(|| HitRecord::_new(r.at(t), Vec3::new(0.0, 1.0, 0.0), t, true))
+        }
+    }
+
+    enum Shape {
+        Sphere(Sphere),
+        Floor(Floor),
+    }
+
+    impl Hittable for Shape {
+        fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+            match self {
+                Shape::Sphere(sphere) => sphere.hit(r, t_min, t_max),
+                Shape::Floor(floor) => floor.hit(r, t_min, t_max),
+            }
+        }
+
+        fn bounding_box(&self) -> Option<Aabb> {
+            match self {
+                Shape::Sphere(sphere) => sphere.bounding_box(),
+                Shape::Floor(floor) => floor.bounding_box(),
+            }
+        }
+    }
+
+    #[test]
+    fn unbounded_primitives() {
+        let mut rng = Pcg32::new(3, 0);
+        let mut shapes = (0..100)
+            .map(|_| Shape::Sphere(sphere(random_point(&mut rng, 10.0), 1.0)))
+            .collect::<Vec<_>>();
+        shapes.insert(50, Shape::Floor(Floor));
+        let centers = shapes
+            .iter()
+            .filter_map(|shape| shape.bounding_box().map(|bounds| bounds.centroid()))
+            .collect::<Vec<_>>();
+        let rays = rays(&centers);
+        let expected = rays
+            .iter()
+            .map(|ray| {
+                let hits = shapes
+                    .iter()
+                    .filter_map(|shape| shape.hit(ray, 0.001, f64::INFINITY));
+                hits.map(|rec| rec.t).min_by(f64::total_cmp)
+            })
+            .collect::<Vec<_>>();
+
+        let bvh = Bvh::new(shapes);
+        assert!(bvh.bounding_box().is_none());
+        for (ray, expected) in rays.iter().zip(expected) {
+            assert_eq!(
+                bvh.hit(ray, 0.001, f64::INFINITY).map(|rec| rec.t),
+                expected
+            );
+        }
+    }
+}
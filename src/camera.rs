@@ -1,3 +1,5 @@
+use crate::sampler::Sampler;
+use crate::sampling::concentric_disk;
 use crate::{Point3, Ray, Vec3};
 
 struct CameraBasis {
@@ -13,6 +15,9 @@ pub struct Camera {
     lower_left_corner: Point3,
     uvw: CameraBasis,
     lens_radius: f64,
+    // rays are sent at random times while the shutter is open
+    shutter_open: f64,
+    shutter_close: f64,
 }
 
 impl Camera {
@@ -47,15 +52,84 @@ impl Camera {
             lower_left_corner,
             lens_radius,
             uvw: CameraBasis { u, v, _w: w },
+            shutter_open: 0.0,
+            shutter_close: 1.0,
         }
     }
 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rd = self.lens_radius * Vec3::random_in_unit_disk();
+    // Objects move during the frame from time 0 to 1, by default the shutter is
+    // open for the whole frame
+    pub fn shutter(self, open: f64, close: f64) -> Self {
+        Camera {
+            shutter_open: open,
+            shutter_close: close,
+            ..self
+        }
+    }
+
+    // The sampler picks the point on the lens and the time
+    pub fn get_ray(&self, s: f64, t: f64, sampler: &mut impl Sampler) -> Ray {
+        let rd = self.lens_radius * concentric_disk(sampler.get_2d());
         let offset = self.uvw.u * rd.x + self.uvw.v * rd.y;
+        let u = sampler.get_1d();
+        let time = if self.shutter_close > self.shutter_open {
+            self.shutter_open + u * (self.shutter_close - self.shutter_open)
+        } else {
+            self.shutter_open
+        };
         Ray::new(
             self.origin + offset,
             self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
+            time,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::{SamplerKind, Samplers};
+
+    fn camera() -> Camera {
+        Camera::new(
+            Point3::new(0.0, 0.0, 5.0),
+            Point3::ZERO,
+            Vec3::new(0.0, 1.0, 0.0),
+            40.0,
+            1.5,
+            0.1,
+            5.0,
         )
     }
+
+    // Times of the rays of count samples through the centre of one pixel
+    fn ray_times(camera: &Camera, count: u32) -> Vec<f64> {
+        let mut sampler = Samplers::new(SamplerKind::Stratified, count, 3);
+        (0..count)
+            .map(|index| {
+                sampler.start_pixel_sample(11, index);
+                camera.get_ray(0.5, 0.5, &mut sampler).time()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ray_times_cover_the_open_shutter() {
+        let camera = camera().shutter(0.25, 0.75);
+        let times = ray_times(&camera, 64);
+        for &time in &times {
+            assert!((0.25..=0.75).contains(&time), "{time}");
+        }
+        let earliest = times.iter().copied().fold(f64::INFINITY, f64::min);
+        let latest = times.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        assert!(earliest < 0.3 && latest > 0.7, "{earliest} to {latest}");
+    }
+
+    #[test]
+    fn closed_shutter_gives_a_single_time() {
+        let camera = camera().shutter(0.4, 0.4);
+        for time in ray_times(&camera, 16) {
+            assert_eq!(time, 0.4);
+        }
+    }
 }
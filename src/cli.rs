@@ -0,0 +1,215 @@
+use clap::Parser;
+
+use crate::sampler::SamplerKind;
+use crate::scenes::BUILTIN_SCENES;
+use crate::settings::{OutputFormat, RenderSettings};
+use crate::tile::TileOrder;
+
+/// Path tracer based on Ray Tracing in One Weekend
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[arg(default_value = "final", help = scene_help())]
+    pub scene: String,
+
+    /// Image width in pixels
+    #[arg(short, long)]
+    pub width: Option<u32>,
+
+    /// Image width divided by height
+    #[arg(short, long)]
+    pub aspect_ratio: Option<f64>,
+
+    /// Samples per pixel
+    #[arg(short, long)]
+    pub samples: Option<u32>,
+
+    /// Keep sampling pixels until their estimated error is below this fraction of
+    /// the brightness range, --samples is then the minimum per pixel
+    #[arg(long)]
+    pub adaptive_threshold: Option<f64>,
+
+    /// Most samples per pixel with an adaptive threshold, four times --samples by default
+    #[arg(long)]
+    pub max_samples: Option<u32>,
+
+    /// Most bounces of a ray, only a safety limit as paths end at random anyway
+    #[arg(short = 'd', long)]
+    pub max_depth: Option<i32>,
+
+    /// Bounces before paths carrying little light may be ended at random
+    #[arg(long)]
+    pub roulette_depth: Option<i32>,
+
+    /// Output path, a .png or .ppm extension also selects the format
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    /// Image format written to the output path
+    #[arg(short, long, value_enum)]
+    pub format: Option<OutputFormat>,
+
+    /// Number of render threads, all cores by default
+    #[arg(short = 'j', long)]
+    pub threads: Option<usize>,
+
+    /// Seed of the random numbers, the same seed gives the same image
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// How the random numbers of the samples in a pixel are chosen
+    #[arg(long, value_enum)]
+    pub sampler: Option<SamplerKind>,
+
+    /// Edge length in pixels of the tiles the threads render
+    #[arg(long)]
+    pub tile_size: Option<u32>,
+
+    /// Order in which the tiles are rendered
+    #[arg(long, value_enum)]
+    pub tile_order: Option<TileOrder>,
+
+    /// Write the unfinished image every this many seconds
+    #[arg(long)]
+    pub save_interval: Option<f64>,
+}
+
+// Lists the scenes from the table the loader uses, so the help cannot go stale
+fn scene_help() -> String {
+    format!(
+        "Built-in scene ({}) or path to a TOML scene file",
+        BUILTIN_SCENES.join(", ")
+    )
+}
+
+impl Cli {
+    // Options given on the command line take precedence over the scene
+    pub fn apply(&self, settings: &mut RenderSettings) {
+        if let Some(width) = self.width {
+            settings.image_width = width;
+        }
+        if let Some(aspect_ratio) = self.aspect_ratio {
+            settings.aspect_ratio = aspect_ratio;
+        }
+        if let Some(samples) = self.samples {
+            settings.samples_per_pixel = samples;
+        }
+        if let Some(threshold) = self.adaptive_threshold {
+            settings.adaptive_threshold = Some(threshold);
+        }
+        if let Some(max_samples) = self.max_samples {
+            settings.max_samples_per_pixel = Some(max_samples);
+        }
+        if let Some(max_depth) = self.max_depth {
+            settings.max_depth = max_depth;
+        }
+        if let Some(roulette_depth) = self.roulette_depth {
+            settings.roulette_depth = roulette_depth;
+        }
+        if let Some(seed) = self.seed {
+            settings.seed = seed;
+        }
+        if let Some(sampler) = self.sampler {
+            settings.sampler = sampler;
+        }
+        if let Some(tile_size) = self.tile_size {
+            settings.tile_size = tile_size;
+        }
+        if let Some(tile_order) = self.tile_order {
+            settings.tile_order = tile_order;
+        }
+        if let Some(interval) = self.save_interval {
+            settings.save_interval = Some(interval);
+        }
+        if let Some(output) = &self.output {
+            settings.set_output(output);
+        }
+        if let Some(format) = self.format {
+            settings.format = format;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene_file::parse_scene;
+    use clap::CommandFactory;
+    use std::path::Path;
+
+    const SCENE: &str = r#"
+[render]
+width = 200
+samples_per_pixel = 50
+seed = 7
+output = "renders/scene.ppm"
+
+[camera]
+look_from = [0.0, 0.0, 5.0]
+look_at = [0.0, 0.0, 0.0]
+vertical_fov = 40.0
+"#;
+
+    // Render settings of the scene file with the arguments applied on top
+    fn settings(arguments: &[&str]) -> RenderSettings {
+        let cli = Cli::try_parse_from(["rayrust", "scene.toml"].iter().chain(arguments)).unwrap();
+        let (_, settings) = parse_scene(SCENE, Path::new("scene.toml"), |settings| {
+            cli.apply(settings)
+        })
+        .unwrap();
+        settings
+    }
+
+    #[test]
+    fn help_lists_every_builtin_scene() {
+        let command = Cli::command();
+        let scene = command
+            .get_arguments()
+            .find(|argument| argument.get_id() == "scene")
+            .unwrap();
+        let help = scene.get_help().unwrap().to_string();
+        for name in BUILTIN_SCENES {
+            assert!(help.contains(name), "{help}");
+        }
+    }
+
+    #[test]
+    fn scene_settings_are_kept_without_arguments() {
+        let settings = settings(&[]);
+        assert_eq!(settings.image_width, 200);
+        assert_eq!(settings.samples_per_pixel, 50);
+        assert_eq!(settings.seed, 7);
+        assert_eq!(settings.output, "renders/scene");
+        assert!(settings.format == OutputFormat::Ppm);
+    }
+
+    #[test]
+    fn arguments_override_only_their_settings() {
+        let settings = settings(&["--width", "100", "--seed", "3"]);
+        assert_eq!(settings.image_width, 100);
+        assert_eq!(settings.seed, 3);
+        assert_eq!(settings.samples_per_pixel, 50);
+        assert_eq!(settings.output, "renders/scene");
+    }
+
+    #[test]
+    fn output_extension_replaces_the_scene_format() {
+        let settings = settings(&["--output", "out/image.png"]);
+        assert_eq!(settings.output, "out/image");
+        assert!(settings.format == OutputFormat::Png);
+    }
+
+    #[test]
+    fn output_without_extension_keeps_the_scene_format() {
+        let settings = settings(&["--output", "out/image"]);
+        assert_eq!(settings.output, "out/image");
+        assert!(settings.format == OutputFormat::Ppm);
+    }
+
+    #[test]
+    fn format_argument_wins_over_the_output_extension() {
+        let settings = settings(&["--output", "out/image.png", "--format", "both"]);
+        assert_eq!(settings.output, "out/image");
+        assert!(settings.format == OutputFormat::Both);
+    }
+}
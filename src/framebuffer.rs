@@ -0,0 +1,50 @@
+use std::sync::Mutex;
+
+use crate::tile::Tile;
+use crate::Color;
+
+// Image the render threads write finished tiles into, rows from the top. Pixels
+// not rendered yet are black.
+pub struct Framebuffer {
+    width: u32,
+    pixels: Mutex<Pixels>,
+}
+
+// Mean color and number of samples of every pixel
+pub struct Pixels {
+    pub colors: Vec<Color>,
+    pub sample_counts: Vec<u32>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        let count = width as usize * height as usize;
+        Framebuffer {
+            width,
+            pixels: Mutex::new(Pixels {
+                colors: vec![Color::ZERO; count],
+                sample_counts: vec![0; count],
+            }),
+        }
+    }
+
+    // The tile's pixels row by row
+    pub fn write_tile(&self, tile: &Tile, pixels: &[(Color, u32)]) {
+        let mut framebuffer = self.pixels.lock().unwrap();
+        for (row, line) in pixels.chunks(tile.width as usize).enumerate() {
+            let start = (tile.y as usize + row) * self.width as usize + tile.x as usize;
+            for (offset, &(color, count)) in line.iter().enumerate() {
+                framebuffer.colors[start + offset] = color;
+                framebuffer.sample_counts[start + offset] = count;
+            }
+        }
+    }
+
+    pub fn colors(&self) -> Vec<Color> {
+        self.pixels.lock().unwrap().colors.clone()
+    }
+
+    pub fn into_pixels(self) -> Pixels {
+        self.pixels.into_inner().unwrap()
+    }
+}
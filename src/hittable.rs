@@ -1,4 +1,13 @@
+use crate::aabb::Aabb;
+use crate::bvh::Bvh;
+use crate::hittable_list::HittableList;
 use crate::material::{EmptyMaterial, Materials};
+use crate::medium::{ConstantMedium, HeterogeneousMedium};
+use crate::mesh::Mesh;
+use crate::quad::{Cuboid, Quad};
+use crate::sphere::{MovingSphere, Sphere};
+use crate::transform::Instance;
+use crate::triangle::Triangle;
 use crate::vec3::dot;
 use crate::{Point3, Ray, Vec3};
 
@@ -9,6 +18,11 @@ pub struct HitRecord<'a> {
     pub material: &'a Materials,
     pub t: f64,
     pub front_face: bool,
+    // surface coordinates of the hit point
+    pub u: f64,
+    pub v: f64,
+    // weights of the triangle vertices, zero for other shapes
+    pub barycentric: Vec3,
 }
 
 impl<'a> HitRecord<'a> {
@@ -28,6 +42,9 @@ impl<'a> HitRecord<'a> {
         material: &Materials::EmptyMaterial(EmptyMaterial {}),
         t: 0.0,
         front_face: false,
+        u: 0.0,
+        v: 0.0,
+        barycentric: Vec3::ZERO,
     };
 
     pub fn _new(p: Vec3, normal: Vec3, t: f64, front_face: bool) -> Self {
@@ -37,12 +54,135 @@ impl<'a> HitRecord<'a> {
             material: &Materials::EmptyMaterial(EmptyMaterial {}),
             t,
             front_face,
+            u: 0.0,
+            v: 0.0,
+            barycentric: Vec3::ZERO,
         }
     }
 }
 
 pub trait Hittable {
-    fn hit(&self, _r: &Ray, _t_min: f64, _t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, _r: &Ray, _t_min: f64, _t_max: f64) -> Option<HitRecord<'_>> {
         None
     }
+
+    // Objects without finite extent (e.g. infinite planes) return None
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+// Every kind of object that can be placed in the world
+pub enum Hittables {
+    Sphere(Sphere),
+    MovingSphere(MovingSphere),
+    Triangle(Triangle),
+    Mesh(Mesh),
+    Quad(Quad),
+    Cuboid(Cuboid),
+    Instance(Instance),
+    ConstantMedium(ConstantMedium),
+    HeterogeneousMedium(HeterogeneousMedium),
+    List(HittableList),
+    Bvh(Bvh<Hittables>),
+}
+
+impl Hittable for Hittables {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        match self {
+            Hittables::Sphere(sphere) => sphere.hit(r, t_min, t_max),
+            Hittables::MovingSphere(sphere) => sphere.hit(r, t_min, t_max),
+            Hittables::Triangle(triangle) => triangle.hit(r, t_min, t_max),
+            Hittables::Mesh(mesh) => mesh.hit(r, t_min, t_max),
+            Hittables::Quad(quad) => quad.hit(r, t_min, t_max),
+            Hittables::Cuboid(cuboid) => cuboid.hit(r, t_min, t_max),
+            Hittables::Instance(instance) => instance.hit(r, t_min, t_max),
+            Hittables::ConstantMedium(medium) => medium.hit(r, t_min, t_max),
+            Hittables::HeterogeneousMedium(medium) => medium.hit(r, t_min, t_max),
+            Hittables::List(list) => list.hit(r, t_min, t_max),
+            Hittables::Bvh(bvh) => bvh.hit(r, t_min, t_max),
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        match self {
+            Hittables::Sphere(sphere) => sphere.bounding_box(),
+            Hittables::MovingSphere(sphere) => sphere.bounding_box(),
+            Hittables::Triangle(triangle) => triangle.bounding_box(),
+            Hittables::Mesh(mesh) => mesh.bounding_box(),
+            Hittables::Quad(quad) => quad.bounding_box(),
+            Hittables::Cuboid(cuboid) => cuboid.bounding_box(),
+            Hittables::Instance(instance) => instance.bounding_box(),
+            Hittables::ConstantMedium(medium) => medium.bounding_box(),
+            Hittables::HeterogeneousMedium(medium) => medium.bounding_box(),
+            Hittables::List(list) => list.bounding_box(),
+            Hittables::Bvh(bvh) => bvh.bounding_box(),
+        }
+    }
+}
+
+impl From<Sphere> for Hittables {
+    fn from(sphere: Sphere) -> Self {
+        Hittables::Sphere(sphere)
+    }
+}
+
+impl From<MovingSphere> for Hittables {
+    fn from(sphere: MovingSphere) -> Self {
+        Hittables::MovingSphere(sphere)
+    }
+}
+
+impl From<Triangle> for Hittables {
+    fn from(triangle: Triangle) -> Self {
+        Hittables::Triangle(triangle)
+    }
+}
+
+impl From<Mesh> for Hittables {
+    fn from(mesh: Mesh) -> Self {
+        Hittables::Mesh(mesh)
+    }
+}
+
+impl From<Quad> for Hittables {
+    fn from(quad: Quad) -> Self {
+        Hittables::Quad(quad)
+    }
+}
+
+impl From<Cuboid> for Hittables {
+    fn from(cuboid: Cuboid) -> Self {
+        Hittables::Cuboid(cuboid)
+    }
+}
+
+impl From<Instance> for Hittables {
+    fn from(instance: Instance) -> Self {
+        Hittables::Instance(instance)
+    }
+}
+
+impl From<ConstantMedium> for Hittables {
+    fn from(medium: ConstantMedium) -> Self {
+        Hittables::ConstantMedium(medium)
+    }
+}
+
+impl From<HeterogeneousMedium> for Hittables {
+    fn from(medium: HeterogeneousMedium) -> Self {
+        Hittables::HeterogeneousMedium(medium)
+    }
+}
+
+impl From<HittableList> for Hittables {
+    fn from(list: HittableList) -> Self {
+        Hittables::List(list)
+    }
+}
+
+impl From<Bvh<Hittables>> for Hittables {
+    fn from(bvh: Bvh<Hittables>) -> Self {
+        Hittables::Bvh(bvh)
+    }
 }
@@ -1,31 +1,40 @@
-use crate::hittable::{HitRecord, Hittable};
-use crate::sphere::Sphere;
+use crate::aabb::Aabb;
+use crate::bvh::Bvh;
+use crate::hittable::{HitRecord, Hittable, Hittables};
 use crate::Ray;
 
 pub struct HittableList {
-    objects: Vec<Sphere>,
+    objects: Vec<Hittables>,
 }
 
 impl HittableList {
     pub const EMPTY: HittableList = HittableList { objects: vec![] };
 
-    pub fn _new(object: Sphere) -> Self {
+    pub fn _new(object: impl Into<Hittables>) -> Self {
         HittableList {
-            objects: vec![object],
+            objects: vec![object.into()],
         }
     }
 
-    pub fn add(&mut self, object: Sphere) {
-        self.objects.push(object);
+    pub fn add(&mut self, object: impl Into<Hittables>) {
+        self.objects.push(object.into());
     }
 
     pub fn _clear(&mut self) {
         self.objects.clear();
     }
+
+    pub fn objects(&self) -> &[Hittables] {
+        &self.objects
+    }
+
+    pub fn into_bvh(self) -> Bvh<Hittables> {
+        Bvh::new(self.objects)
+    }
 }
 
 impl Hittable for HittableList {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let mut hit_anything = None;
         let mut closest_so_far = t_max;
 
@@ -38,4 +47,80 @@ impl Hittable for HittableList {
 
         hit_anything
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if self.objects.is_empty() {
+            return None;
+        }
+        let mut output_box = Aabb::EMPTY;
+        for object in self.objects.as_slice() {
+            output_box = Aabb::surrounding_box(&output_box, &object.bounding_box()?);
+        }
+        Some(output_box)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{EmptyMaterial, Materials};
+    use crate::quad::{Cuboid, Quad};
+    use crate::sphere::Sphere;
+    use crate::{Point3, Vec3};
+
+    fn material() -> Materials {
+        Materials::EmptyMaterial(EmptyMaterial {})
+    }
+
+    // Objects along the negative z axis, the closest one inside a nested list
+    // between two that are further away
+    fn world() -> HittableList {
+        let mut nested = HittableList::EMPTY;
+        nested.add(Quad::new(
+            Point3::new(-1.0, -1.0, -3.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            material(),
+        ));
+        nested.add(Sphere::new(Point3::new(0.0, 0.0, -20.0), 1.0, material()));
+
+        let mut world = HittableList::EMPTY;
+        world.add(Sphere::new(Point3::new(0.0, 0.0, -10.0), 1.0, material()));
+        world.add(nested);
+        world.add(Cuboid::new(
+            Point3::new(-2.0, -1.0, -6.0),
+            Point3::new(1.0, 3.0, -4.0),
+            material(),
+        ));
+        world
+    }
+
+    #[test]
+    fn closest_hit_is_returned() {
+        let ray = Ray::new(Point3::ZERO, Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let world = world();
+        let rec = world.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-12, "{}", rec.t);
+
+        // Without the nested list in reach the box in front of the sphere is hit
+        let rec = world.hit(&ray, 3.5, f64::INFINITY).unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-12, "{}", rec.t);
+    }
+
+    #[test]
+    fn bounding_box_is_the_union_of_all_objects() {
+        let bounds = world().bounding_box().unwrap();
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-3;
+        assert!(close(bounds.minimum.x, -2.0), "{}", bounds.minimum.x);
+        assert!(close(bounds.minimum.y, -1.0), "{}", bounds.minimum.y);
+        assert!(close(bounds.minimum.z, -21.0), "{}", bounds.minimum.z);
+        assert!(close(bounds.maximum.x, 1.0), "{}", bounds.maximum.x);
+        assert!(close(bounds.maximum.y, 3.0), "{}", bounds.maximum.y);
+        assert!(close(bounds.maximum.z, -3.0), "{}", bounds.maximum.z);
+    }
+
+    #[test]
+    fn empty_list_has_no_bounds() {
+        assert!(HittableList::EMPTY.bounding_box().is_none());
+    }
 }
@@ -0,0 +1,276 @@
+use crate::hittable::{HitRecord, Hittable, Hittables};
+use crate::material::{Material, Materials};
+use crate::medium::MediumList;
+use crate::quad::Quad;
+use crate::sampler::Sampler;
+use crate::sphere::Sphere;
+use crate::vec3::Onb;
+use crate::{Color, Point3, Ray, Vec3};
+
+// Emitter that can be sampled directly, it borrows the object from the world
+#[derive(Copy, Clone)]
+pub enum Light<'a> {
+    Sphere(&'a Sphere),
+    Quad(&'a Quad),
+}
+
+impl<'a> Light<'a> {
+    fn material(&self) -> &'a Materials {
+        match self {
+            Light::Sphere(sphere) => &sphere.material,
+            Light::Quad(quad) => &quad.material,
+        }
+    }
+
+    // Direction from origin towards a random point of the light and its pdf
+    // with respect to solid angle
+    fn sample(&self, origin: Point3, u: [f64; 2]) -> (Vec3, f64) {
+        match self {
+            Light::Sphere(sphere) => sphere.sample_direction(origin, u),
+            Light::Quad(quad) => quad.sample_direction(origin, u),
+        }
+    }
+
+    fn pdf(&self, origin: Point3, point: Point3) -> f64 {
+        match self {
+            Light::Sphere(sphere) => sphere.direction_pdf(origin, point),
+            Light::Quad(quad) => quad.direction_pdf(origin, point),
+        }
+    }
+}
+
+// Weight of a sample taken with pdf when other_pdf is the density of the other
+// strategy, the weights of both strategies add up to one
+pub fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b > 0.0 {
+        a / (a + b)
+    } else {
+        0.0
+    }
+}
+
+// Spheres and quads with an emitting material, found anywhere in the world except
+// inside instances. Other emitters are only reached by scattered rays.
+pub struct LightList<'a> {
+    lights: Vec<Light<'a>>,
+}
+
+impl<'a> LightList<'a> {
+    pub fn new(world: &'a Hittables) -> Self {
+        let mut lights = Vec::new();
+        collect_lights(world, &mut lights);
+        LightList { lights }
+    }
+
+    // Density with which direct_light picks the direction from origin to the hit point
+    // of a light, None when the object hit is not one of the lights
+    pub fn pdf(&self, origin: Point3, light_rec: &HitRecord) -> Option<f64> {
+        let light = self
+            .lights
+            .iter()
+            .find(|light| std::ptr::eq(light.material(), light_rec.material))?;
+        Some(light.pdf(origin, light_rec.p) / self.lights.len() as f64)
+    }
+
+    // Light arriving at the hit point straight from one random light and leaving
+    // back along ray_in, weighted against finding the same light with a sampled direction.
+    // Sharp reflections only find lights by sampling.
+    pub fn direct_light(
+        &self,
+        world: &impl Hittable,
+        media: &MediumList,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut impl Sampler,
+    ) -> Color {
+        if self.lights.is_empty() {
+            return Color::ZERO;
+        }
+
+        // Each light is picked with probability 1 / count
+        let count = self.lights.len();
+        let index = ((sampler.get_1d() * count as f64) as usize).min(count - 1);
+        let light = self.lights[index];
+        let (direction, pdf) = light.sample(rec.p, sampler.get_2d());
+        let pdf = pdf / count as f64;
+
+        let frame = Onb::from_w(rec.normal);
+        let wo = frame.to_local(-ray_in.direction().unit_vector());
+        let wi = frame.to_local(direction);
+        let scattering = rec.material.eval(rec, wo, wi);
+        if !(pdf > 0.0 && pdf.is_finite()) || scattering.near_zero() {
+            return Color::ZERO;
+        }
+
+        // Shadow ray, the light is visible when nothing else is in front of it.
+        // Constant media in between block it at random, as often as they absorb or
+        // scatter the light, heterogeneous ones let an estimated fraction through.
+        let shadow_ray = Ray::new(rec.p, direction, ray_in.time());
+        match world.hit(&shadow_ray, 0.001, f64::INFINITY) {
+            Some(light_rec) if std::ptr::eq(light_rec.material, light.material()) => {
+                let transmittance = media.transmittance(&shadow_ray, 0.001, light_rec.t);
+                let emitted = light_rec.material.emitted(&light_rec);
+                let weight = power_heuristic(pdf, rec.material.pdf(rec, wo, wi));
+                transmittance * weight * scattering * emitted / pdf
+            }
+            _ => Color::ZERO,
+        }
+    }
+}
+
+fn collect_lights<'a>(object: &'a Hittables, lights: &mut Vec<Light<'a>>) {
+    let emits = |material: &Materials| matches!(material, Materials::DiffuseLight(_));
+    match object {
+        Hittables::Sphere(sphere) if emits(&sphere.material) => lights.push(Light::Sphere(sphere)),
+        Hittables::Quad(quad) if emits(&quad.material) => lights.push(Light::Quad(quad)),
+        Hittables::Cuboid(cuboid) => lights.extend(
+            cuboid
+                .sides()
+                .iter()
+                .filter(|side| emits(&side.material))
+                .map(Light::Quad),
+        ),
+        Hittables::List(list) => {
+            for object in list.objects() {
+                collect_lights(object, lights);
+            }
+        }
+        Hittables::Bvh(bvh) => {
+            for object in bvh.objects() {
+                collect_lights(object, lights);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable_list::HittableList;
+    use crate::material::{DiffuseLight, Lambertian};
+
+    // Hands out the same number for every dimension
+    struct FixedSampler(f64);
+
+    impl Sampler for FixedSampler {
+        fn start_pixel_sample(&mut self, _pixel: u64, _index: u32) {}
+
+        fn get_1d(&mut self) -> f64 {
+            self.0
+        }
+
+        fn get_2d(&mut self) -> [f64; 2] {
+            [self.0, self.0]
+        }
+    }
+
+    fn light() -> Materials {
+        Materials::DiffuseLight(DiffuseLight::new(Color::new(4.0, 4.0, 4.0)))
+    }
+
+    fn white() -> Materials {
+        Materials::Lambertian(Lambertian::new(&Color::new(0.5, 0.5, 0.5)))
+    }
+
+    // Light facing down onto the floor at y = 0 from y = 2
+    fn ceiling_light() -> Quad {
+        Quad::new(
+            Point3::new(-1.0, 2.0, -1.0),
+            Vec3::x(2.0),
+            Vec3::z(2.0),
+            light(),
+        )
+    }
+
+    // Floor at y = 0 facing up
+    fn floor() -> Quad {
+        Quad::new(
+            Point3::new(-5.0, 0.0, -5.0),
+            Vec3::z(10.0),
+            Vec3::x(10.0),
+            white(),
+        )
+    }
+
+    fn world(objects: impl IntoIterator<Item = Hittables>) -> Hittables {
+        let mut list = HittableList::EMPTY;
+        for object in objects {
+            list.add(object);
+        }
+        Hittables::List(list)
+    }
+
+    #[test]
+    fn power_heuristic_weights_add_up_to_one() {
+        for (a, b) in [(1.0, 1.0), (0.3, 2.5), (10.0, 0.01), (0.0, 4.0)] {
+            let sum = power_heuristic(a, b) + power_heuristic(b, a);
+            assert!((sum - 1.0).abs() < 1e-12, "{sum}");
+        }
+        assert_eq!(power_heuristic(0.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn pdf_matches_the_density_lights_are_sampled_with() {
+        let world = world([
+            ceiling_light().into(),
+            Sphere::new(Point3::new(5.0, 0.0, 0.0), 1.0, light()).into(),
+        ]);
+        let lights = LightList::new(&world);
+        assert_eq!(lights.lights.len(), 2);
+
+        let origin = Point3::new(0.2, 0.0, -0.3);
+        for light in &lights.lights {
+            for u in [[0.1, 0.7], [0.5, 0.5], [0.9, 0.2]] {
+                let (direction, pdf) = light.sample(origin, u);
+                let ray = Ray::new(origin, direction, 0.0);
+                let rec = world.hit(&ray, 0.001, f64::INFINITY).unwrap();
+                assert!(std::ptr::eq(rec.material, light.material()));
+
+                // Either light is picked half of the time
+                let expected = pdf / 2.0;
+                let found = lights.pdf(origin, &rec).unwrap();
+                assert!(
+                    (found - expected).abs() < 1e-9 * expected,
+                    "{found} != {expected}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn pdf_is_none_for_objects_that_are_not_lights() {
+        let world = world([ceiling_light().into()]);
+        let lights = LightList::new(&world);
+        let floor = floor();
+        let ray = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::y(-1.0), 0.0);
+        let rec = floor.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!(lights.pdf(ray.origin(), &rec).is_none());
+    }
+
+    #[test]
+    fn occluded_light_gives_no_direct_light() {
+        let surface = floor();
+        let ray = Ray::new(Point3::new(0.0, 1.0, -1.0), Vec3::new(0.0, -1.0, 1.0), 0.0);
+        let rec = surface.hit(&ray, 0.001, f64::INFINITY).unwrap();
+
+        let open = world([ceiling_light().into(), floor().into()]);
+        let lights = LightList::new(&open);
+        let media = MediumList::new(&open);
+        let direct = lights.direct_light(&open, &media, &ray, &rec, &mut FixedSampler(0.5));
+        assert!(direct.x > 0.0, "{}", direct.x);
+
+        let blocker = Quad::new(
+            Point3::new(-0.5, 1.0, -0.5),
+            Vec3::z(1.0),
+            Vec3::x(1.0),
+            white(),
+        );
+        let blocked = world([ceiling_light().into(), floor().into(), blocker.into()]);
+        let lights = LightList::new(&blocked);
+        let media = MediumList::new(&blocked);
+        let direct = lights.direct_light(&blocked, &media, &ray, &rec, &mut FixedSampler(0.5));
+        assert!(direct.near_zero(), "{}", direct.x);
+    }
+}
@@ -1,162 +1,431 @@
 #![warn(rust_2018_idioms)]
 #![allow(elided_lifetimes_in_paths)]
 
+use std::error::Error;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use clap::Parser;
 use rayon::prelude::*;
 
-use crate::camera::Camera;
-use crate::hittable::Hittable;
-use crate::hittable_list::HittableList;
-use crate::material::{Dielectric, Lambertian, Material, Materials, Metal};
+use crate::adaptive::PixelStats;
+use crate::background::Background;
+use crate::cli::Cli;
+use crate::framebuffer::{Framebuffer, Pixels};
+use crate::hittable::{Hittable, Hittables};
+use crate::light::{power_heuristic, LightList};
+use crate::material::Material;
+use crate::medium::MediumList;
 use crate::ray::Ray;
-use crate::sphere::Sphere;
+use crate::sampler::{Sampler, Samplers};
+use crate::scene_file::load_scene_file;
+use crate::scenes::{Scene, BUILTIN_SCENES};
+use crate::settings::{OutputFormat, RenderSettings};
+use crate::tile::tiles;
 use crate::utilities::{
-    random_double, random_double_from_range, save_image_as_png, save_image_as_ppm,
+    save_image_as_png, save_image_as_ppm, save_sample_map, seed_random, seed_sample,
 };
-use crate::vec3::{Color, Point3, Vec3};
+use crate::vec3::{Color, Onb, Point3, Vec3};
 
+mod aabb;
+mod adaptive;
+mod background;
+mod bvh;
 mod camera;
+mod cli;
+mod framebuffer;
 mod hittable;
 mod hittable_list;
+mod light;
 mod material;
+mod medium;
+mod mesh;
+mod mtl;
+mod noise;
+mod obj;
+mod phase;
+mod quad;
 mod ray;
+mod rng;
+mod sampler;
+mod sampling;
+mod scene_file;
+mod scenes;
+mod settings;
 mod sphere;
+mod texture;
+mod tile;
+mod transform;
+mod triangle;
 mod utilities;
 mod vec3;
+mod voxel;
+
+// What rays are traced against and how long paths may get
+struct Integrator<'a> {
+    background: &'a Background,
+    world: &'a Hittables,
+    lights: &'a LightList<'a>,
+    media: &'a MediumList<'a>,
+    max_depth: i32,
+    roulette_depth: i32,
+}
+
+impl Integrator<'_> {
+    // Light arriving along the camera ray r. After roulette_depth bounces paths end at
+    // random, the more likely the less light they still carry, and the survivors are
+    // scaled up so the mean stays the same. max_depth is a safety cap, every path still
+    // alive after that many bounces ends there.
+    fn ray_color(&self, r: Ray, sampler: &mut impl Sampler) -> Color {
+        let mut radiance = Color::ZERO;
+        // fraction of the light at the current ray that reaches the camera
+        let mut throughput = Color::ONE;
+        let mut ray = r;
+        // density the ray was sampled with at its origin, None for the camera ray and
+        // sharp reflections. Light the ray hits is weighted against direct sampling.
+        let mut scatter_pdf: Option<f64> = None;
+
+        for depth in 0..self.max_depth {
+            // Heterogeneous media are tracked up to the closest surface
+            let surface = self.world.hit(&ray, 0.001, f64::INFINITY);
+            let t_surface = surface.map_or(f64::INFINITY, |rec| rec.t);
+            let collision = self.media.sample_collision(&ray, 0.001, t_surface);
+            let Some(rec) = collision.or(surface) else {
+                radiance += throughput * self.background.color(&ray);
+                break;
+            };
+
+            let mut emitted = rec.material.emitted(&rec);
+            let light_pdf = self.lights.pdf(ray.origin(), &rec);
+            if let (Some(scatter_pdf), Some(light_pdf)) = (scatter_pdf, light_pdf) {
+                emitted = power_heuristic(scatter_pdf, light_pdf) * emitted;
+            }
+
+            let frame = Onb::from_w(rec.normal);
+            let wo = frame.to_local(-ray.direction().unit_vector());
+            let direct = self
+                .lights
+                .direct_light(self.world, self.media, &ray, &rec, sampler);
+            radiance += throughput * (emitted + direct);
 
-fn final_scene() -> HittableList {
-    // World
-    let mut world = HittableList::EMPTY;
-
-    let material_ground = Materials::Lambertian(Lambertian::new(&Color::new(0.5, 0.5, 0.5)));
-    world.add(Sphere::new(
-        Point3::new(0.0, -1000.0, -1.0),
-        1000.0,
-        material_ground,
-    ));
-
-    let center_comparison_pt = Point3::new(4.0, 0.2, 0.0);
-
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_material = random_double();
-
-            let center = Point3::new(
-                (a as f64) + 0.9 * random_double(),
-                0.2,
-                (b as f64) + 0.9 * random_double(),
-            );
-
-            if (center - center_comparison_pt).length() > 0.9 {
-                let sphere_material: Materials;
-
-                if choose_material < 0.8 {
-                    // diffuse
-                    let albedo = Color::random() * Color::random();
-                    sphere_material = Materials::Lambertian(Lambertian { albedo });
-                } else if choose_material < 0.95 {
-                    // metal
-                    let albedo = Color::random_from_range(0.5, 1.0);
-                    let fuzziness = random_double_from_range(0.0, 0.5);
-                    sphere_material = Materials::Metal(Metal::new(albedo, fuzziness));
-                } else {
-                    sphere_material = Materials::Dielectric(Dielectric::new(1.5));
+            let uc = sampler.get_1d();
+            let u = sampler.get_2d();
+            let Some(sample) = rec.material.sample(&rec, wo, uc, u) else {
+                break;
+            };
+            throughput = throughput * sample.weight;
+
+            if depth + 1 >= self.roulette_depth {
+                let survival = throughput.max_component().min(1.0);
+                if survival <= 0.0 || sampler.get_1d() >= survival {
+                    break;
                 }
-                world.add(Sphere::new(center, 0.2, sphere_material));
+                throughput = throughput / survival;
             }
+
+            scatter_pdf = (!sample.specular).then_some(sample.pdf);
+            ray = Ray::new(rec.p, frame.to_world(sample.wi), ray.time());
         }
-    }
 
-    let material_1 = Materials::Dielectric(Dielectric::new(1.5));
-    world.add(Sphere::new(Point3::y(1.0), 1.0, material_1));
+        radiance
+    }
+}
 
-    let material_2 = Materials::Lambertian(Lambertian::new(&Color::new(0.4, 0.4, 0.1)));
-    world.add(Sphere::new(Point3::new(-4.0, 1.0, 0.0), 1.0, material_2));
+fn load_scene(cli: &Cli) -> Result<(Scene, RenderSettings), Box<dyn Error>> {
+    let mut settings = scenes::builtin_settings(&cli.scene);
+    cli.apply(&mut settings);
 
-    let material_3 = Materials::Metal(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
-    world.add(Sphere::new(Point3::new(4.0, 1.0, 0.0), 1.0, material_3));
+    // Generated scenes draw their random objects from the seed
+    seed_random(settings.seed);
+    if let Some(scene) = scenes::builtin_scene(&cli.scene, settings.aspect_ratio) {
+        settings.validate()?;
+        return Ok((scene?, settings));
+    }
 
-    world
+    let path = Path::new(&cli.scene);
+    if !path.exists() {
+        return Err(format!(
+            "'{}' is neither a built-in scene ({}) nor a scene file",
+            cli.scene,
+            BUILTIN_SCENES.join(", ")
+        )
+        .into());
+    }
+    Ok(load_scene_file(path, |settings| cli.apply(settings))?)
 }
 
-fn ray_color(r: &Ray, world: &HittableList, depth: i32) -> Color {
-    if depth <= 0 {
-        return Color::ZERO;
+// Writes the image in the formats the settings ask for, rows from the top
+fn save_image(settings: &RenderSettings, colors: &[Color], image_height: u32) {
+    let image_name = &settings.output;
+    if settings.format != OutputFormat::Png {
+        let ppm_path = &format!("{image_name}.ppm");
+        save_image_as_ppm(ppm_path, colors, settings.image_width, image_height);
     }
 
-    if let Some(rec) = world.hit(r, 0.001, f64::INFINITY) {
-        // scattered and attenuation are changed in material.scatter()
-        return if let Some((scattered, attenuation)) = rec.material.scatter(r, &rec) {
-            attenuation * ray_color(&scattered, world, depth - 1)
-        } else {
-            Color::ZERO
-        };
+    if settings.format != OutputFormat::Ppm {
+        let png_path = &format!("{image_name}.png");
+        save_image_as_png(png_path, colors, settings.image_width, image_height);
     }
-
-    let unit_direction = r.direction().unit_vector();
-    let t = 0.5 * (unit_direction.y + 1.0);
-    (1.0 - t) * Color::ONE + t * Color::new(0.5, 0.7, 1.0)
 }
 
 fn main() {
-    // Image
-    const ASPECT_RATIO: f64 = 3.0 / 2.0;
-    const IMAGE_WIDTH: u32 = 100;
-    const IMAGE_HEIGHT: u32 = (IMAGE_WIDTH as f64 / ASPECT_RATIO) as u32;
-    const SAMPLES_PER_PIXEL: u32 = 500;
-    const MAX_DEPTH: i32 = 50;
-
-    let world = final_scene();
-    // Camera
-    let camera = Camera::new(
-        Vec3::new(13.0, 2.0, 3.0),
-        Vec3::ZERO,
-        Vec3::y(1.0),
-        20.0,
-        ASPECT_RATIO,
-        0.1,
-        10.0,
+    let cli = Cli::parse();
+
+    let mut thread_pool = rayon::ThreadPoolBuilder::new();
+    if let Some(threads) = cli.threads {
+        thread_pool = thread_pool.num_threads(threads);
+    }
+    thread_pool
+        .build_global()
+        .expect("Failed to start the render threads");
+
+    let (scene, settings) = load_scene(&cli).unwrap_or_else(|error| {
+        eprintln!("Failed to load scene: {error}");
+        std::process::exit(1)
+    });
+
+    let image_width = settings.image_width;
+    let image_height = settings.image_height();
+    let samples_per_pixel = settings.samples_per_pixel;
+    let max_samples = settings.max_samples();
+    let adaptive_threshold = settings.adaptive_threshold;
+    let seed = settings.seed;
+    let sampler_kind = settings.sampler;
+
+    let world = Hittables::Bvh(scene.world.into_bvh());
+    let lights = LightList::new(&world);
+    let media = MediumList::new(&world);
+    let camera = scene.camera;
+    let integrator = Integrator {
+        background: &scene.background,
+        world: &world,
+        lights: &lights,
+        media: &media,
+        max_depth: settings.max_depth,
+        roulette_depth: settings.roulette_depth,
+    };
+
+    // Mean color and sample count of the pixel in column i and row j, rows counting
+    // from the bottom
+    let render_pixel = |i: u32, j: u32| {
+        let pixel_index = j as u64 * image_width as u64 + i as u64;
+        let mut sampler = Samplers::new(sampler_kind, samples_per_pixel, seed);
+        let mut stats = PixelStats::default();
+        for sample in 0..max_samples {
+            seed_sample(seed, pixel_index, sample as u64);
+            sampler.start_pixel_sample(pixel_index, sample);
+            let [du, dv] = sampler.get_2d();
+            let u = ((i as f64) + du) / ((image_width - 1) as f64);
+            let v = ((j as f64) + dv) / ((image_height - 1) as f64);
+
+            let r = camera.get_ray(u, v, &mut sampler);
+            stats.add(integrator.ray_color(r, &mut sampler));
+
+            // Checking after every sample would stop more often right
+            // before a rare bright sample, which darkens the image
+            let batch_done = (sample + 1).is_multiple_of(samples_per_pixel);
+            let converged = adaptive_threshold.is_some_and(|threshold| stats.error() < threshold);
+            if batch_done && converged {
+                break;
+            }
+        }
+        (stats.mean(), stats.count())
+    };
+
+    let tiles = tiles(
+        image_width,
+        image_height,
+        settings.tile_size,
+        settings.tile_order,
     );
+    let tile_count = tiles.len();
+    let framebuffer = Framebuffer::new(image_width, image_height);
+    let finished_tiles = AtomicUsize::new(0);
+    let last_save = Mutex::new(Instant::now());
 
-    let mut buffer = Vec::with_capacity((IMAGE_WIDTH * IMAGE_HEIGHT) as usize);
+    // par_bridge hands out the tiles in order to whichever thread is free, so the
+    // image fills in along the tile order. Pixels don't depend on the thread or the
+    // tile they are rendered in.
+    tiles.into_iter().par_bridge().for_each(|tile| {
+        let mut pixels = Vec::with_capacity(tile.width as usize * tile.height as usize);
+        for y in tile.y..tile.y + tile.height {
+            for x in tile.x..tile.x + tile.width {
+                pixels.push(render_pixel(x, image_height - 1 - y));
+            }
+        }
+        framebuffer.write_tile(&tile, &pixels);
 
-    for j in (0..IMAGE_HEIGHT).rev() {
-        if j % 50 == 0 {
-            println!("Line {j}");
+        let finished = finished_tiles.fetch_add(1, Ordering::Relaxed) + 1;
+        if finished.is_multiple_of((tile_count / 10).max(1)) {
+            println!("{finished} of {tile_count} tiles");
         }
-        let line_pixels = (0..IMAGE_WIDTH)
-            .into_par_iter()
-            .map(|i| {
-                let pixel: Color = (0..SAMPLES_PER_PIXEL)
-                    .map(|_| {
-                        let u = ((i as f64) + random_double()) / ((IMAGE_WIDTH - 1) as f64);
-                        let v = ((j as f64) + random_double()) / ((IMAGE_HEIGHT - 1) as f64);
-
-                        let r = &camera.get_ray(u, v);
-                        ray_color(r, &world, MAX_DEPTH)
-                    })
-                    .sum::<Color>();
-                pixel
+
+        // Only one thread saves at a time, the others carry on rendering
+        if let (Some(interval), Ok(mut last_save)) = (settings.save_interval, last_save.try_lock())
+        {
+            if last_save.elapsed().as_secs_f64() >= interval && finished < tile_count {
+                save_image(&settings, &framebuffer.colors(), image_height);
+                *last_save = Instant::now();
+            }
+        }
+    });
+
+    let Pixels {
+        colors,
+        sample_counts,
+    } = framebuffer.into_pixels();
+    save_image(&settings, &colors, image_height);
+
+    let image_name = &settings.output;
+    if adaptive_threshold.is_some() {
+        let total: u64 = sample_counts.iter().map(|&count| count as u64).sum();
+        println!(
+            "Average of {:.1} samples per pixel",
+            total as f64 / sample_counts.len() as f64
+        );
+        save_sample_map(
+            &format!("{image_name}_spp.png"),
+            &sample_counts,
+            max_samples,
+            image_width,
+            image_height,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable_list::HittableList;
+    use crate::material::{DiffuseLight, Lambertian, Materials, Metal};
+    use crate::quad::Quad;
+    use crate::sampler::SamplerKind;
+    use crate::sphere::Sphere;
+
+    fn integrator<'a>(
+        background: &'a Background,
+        world: &'a Hittables,
+        lights: &'a LightList<'a>,
+        media: &'a MediumList<'a>,
+    ) -> Integrator<'a> {
+        Integrator {
+            background,
+            world,
+            lights,
+            media,
+            max_depth: 50,
+            roulette_depth: 50,
+        }
+    }
+
+    fn world(objects: impl IntoIterator<Item = Hittables>) -> Hittables {
+        let mut list = HittableList::EMPTY;
+        for object in objects {
+            list.add(object);
+        }
+        Hittables::List(list)
+    }
+
+    // Mean radiance along the ray over many paths, with roulette from the first bounce
+    fn mean_radiance(world: &Hittables, ray: &Ray, paths: u64) -> f64 {
+        let background = Background::Solid(Color::ONE);
+        let lights = LightList::new(world);
+        let media = MediumList::new(world);
+        let integrator = Integrator {
+            roulette_depth: 1,
+            ..integrator(&background, world, &lights, &media)
+        };
+
+        let mut sampler = Samplers::new(SamplerKind::Independent, 1, 0);
+        let total: f64 = (0..paths)
+            .map(|path| {
+                seed_sample(11, 0, path);
+                let ray = Ray::new(ray.origin(), ray.direction(), 0.0);
+                integrator.ray_color(ray, &mut sampler).x
             })
-            .collect::<Vec<Color>>();
+            .sum();
+        total / paths as f64
+    }
 
-        buffer.extend(line_pixels);
+    // Two mirrors facing each other one unit apart, ten units long
+    fn corridor(albedo: f64) -> Hittables {
+        let metal = || Materials::Metal(Metal::new(Color::ONE * albedo, 0.0));
+        world([
+            Quad::new(Point3::ZERO, Vec3::z(1.0), Vec3::x(10.0), metal()).into(),
+            Quad::new(
+                Point3::new(0.0, 1.0, 0.0),
+                Vec3::x(10.0),
+                Vec3::z(1.0),
+                metal(),
+            )
+            .into(),
+        ])
     }
 
-    let image_name = "final_image_111";
-    save_image_as_ppm(
-        &format!("images/{image_name}.ppm"),
-        &buffer,
-        SAMPLES_PER_PIXEL,
-        IMAGE_WIDTH,
-        IMAGE_HEIGHT,
-    );
+    // Zigzags between the mirrors of the corridor, hitting them ten times
+    fn zigzag() -> Ray {
+        Ray::new(Point3::new(0.25, 0.5, 0.5), Vec3::new(1.0, -1.0, 0.0), 0.0)
+    }
 
-    let png_path = &format!("images/{image_name}.png");
-    save_image_as_png(
-        png_path,
-        &buffer,
-        SAMPLES_PER_PIXEL,
-        IMAGE_WIDTH,
-        IMAGE_HEIGHT,
-    );
+    #[test]
+    fn corridor_without_roulette_dims_by_the_albedo_per_bounce() {
+        let world = corridor(0.8);
+        let background = Background::Solid(Color::ONE);
+        let lights = LightList::new(&world);
+        let media = MediumList::new(&world);
+        let integrator = integrator(&background, &world, &lights, &media);
+        let mut sampler = Samplers::new(SamplerKind::Independent, 1, 0);
+        let color = integrator.ray_color(zigzag(), &mut sampler);
+        assert!((color.x - 0.8_f64.powi(10)).abs() < 1e-12, "{}", color.x);
+    }
+
+    #[test]
+    fn roulette_keeps_the_mean_of_many_bounces() {
+        let mean = mean_radiance(&corridor(0.8), &zigzag(), 20000);
+        let expected = 0.8_f64.powi(10);
+        assert!((mean - expected).abs() < 0.01, "{mean} != {expected}");
+    }
+
+    #[test]
+    fn roulette_keeps_the_mean_of_a_diffuse_sphere() {
+        // Under a uniform sky every ray leaving the convex sphere sees the sky
+        let albedo = Color::ONE * 0.3;
+        let sphere = Sphere::new(
+            Point3::ZERO,
+            1.0,
+            Materials::Lambertian(Lambertian::new(&albedo)),
+        );
+        let world = world([sphere.into()]);
+        let ray = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::z(-1.0), 0.0);
+        let mean = mean_radiance(&world, &ray, 20000);
+        assert!((mean - 0.3).abs() < 0.015, "{mean}");
+    }
+
+    #[test]
+    fn light_seen_in_a_mirror_is_not_weighted() {
+        let mirror = Quad::new(
+            Point3::new(-5.0, 0.0, -5.0),
+            Vec3::z(10.0),
+            Vec3::x(10.0),
+            Materials::Metal(Metal::new(Color::new(0.8, 0.8, 0.8), 0.0)),
+        );
+        // facing down onto the mirror
+        let light = Quad::new(
+            Point3::new(-3.0, 2.0, -3.0),
+            Vec3::x(6.0),
+            Vec3::z(6.0),
+            Materials::DiffuseLight(DiffuseLight::new(Color::new(4.0, 4.0, 4.0))),
+        );
+        let world = world([mirror.into(), light.into()]);
+        let lights = LightList::new(&world);
+        let media = MediumList::new(&world);
+        let integrator = integrator(&Background::BLACK, &world, &lights, &media);
+
+        let mut sampler = Samplers::new(SamplerKind::Independent, 1, 0);
+        let ray = Ray::new(Point3::new(0.0, 1.0, -1.0), Vec3::new(0.0, -1.0, 1.0), 0.0);
+        let color = integrator.ray_color(ray, &mut sampler);
+        assert!((color.x - 3.2).abs() < 1e-9, "{}", color.x);
+    }
 }
@@ -1,93 +1,229 @@
+use std::f64::consts::PI;
+
 use crate::hittable::HitRecord;
-use crate::utilities::random_double;
+use crate::phase::PhaseFunction;
+use crate::sampling::{cosine_hemisphere, cosine_hemisphere_pdf, uniform_sphere};
+use crate::texture::{Texture, Textures};
 use crate::vec3::dot;
-use crate::{Color, Ray, Vec3};
+use crate::{Color, Vec3};
 
+#[derive(Clone)]
 pub enum Materials {
     EmptyMaterial(EmptyMaterial),
     Lambertian(Lambertian),
     Metal(Metal),
     Dielectric(Dielectric),
+    DiffuseLight(DiffuseLight),
+    Volumetric(Volumetric),
 }
 
 impl Material for Materials {
-    fn scatter(&self, _ray_in: &Ray, _rec: &HitRecord) -> Option<(Ray, Color)> {
+    fn eval(&self, rec: &HitRecord, wo: Vec3, wi: Vec3) -> Color {
+        match self {
+            Materials::EmptyMaterial(_) => Color::ZERO,
+            Materials::Lambertian(lambertian) => lambertian.eval(rec, wo, wi),
+            Materials::Metal(metal) => metal.eval(rec, wo, wi),
+            Materials::Dielectric(dielectric) => dielectric.eval(rec, wo, wi),
+            Materials::DiffuseLight(light) => light.eval(rec, wo, wi),
+            Materials::Volumetric(volumetric) => volumetric.eval(rec, wo, wi),
+        }
+    }
+
+    fn pdf(&self, rec: &HitRecord, wo: Vec3, wi: Vec3) -> f64 {
+        match self {
+            Materials::EmptyMaterial(_) => 0.0,
+            Materials::Lambertian(lambertian) => lambertian.pdf(rec, wo, wi),
+            Materials::Metal(metal) => metal.pdf(rec, wo, wi),
+            Materials::Dielectric(dielectric) => dielectric.pdf(rec, wo, wi),
+            Materials::DiffuseLight(light) => light.pdf(rec, wo, wi),
+            Materials::Volumetric(volumetric) => volumetric.pdf(rec, wo, wi),
+        }
+    }
+
+    fn sample(&self, rec: &HitRecord, wo: Vec3, uc: f64, u: [f64; 2]) -> Option<ScatterSample> {
         match self {
             Materials::EmptyMaterial(_) => None,
-            Materials::Lambertian(lambertian) => lambertian.scatter(_ray_in, _rec),
-            Materials::Metal(metal) => metal.scatter(_ray_in, _rec),
-            Materials::Dielectric(dielectric) => dielectric.scatter(_ray_in, _rec),
+            Materials::Lambertian(lambertian) => lambertian.sample(rec, wo, uc, u),
+            Materials::Metal(metal) => metal.sample(rec, wo, uc, u),
+            Materials::Dielectric(dielectric) => dielectric.sample(rec, wo, uc, u),
+            Materials::DiffuseLight(light) => light.sample(rec, wo, uc, u),
+            Materials::Volumetric(volumetric) => volumetric.sample(rec, wo, uc, u),
+        }
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        match self {
+            Materials::DiffuseLight(light) => light.emitted(rec),
+            _ => Color::ZERO,
         }
     }
 }
 
+// Directions are unit vectors in the shading frame of the hit point, where the
+// normal is +z and faces the incoming ray. wo points back along the incoming ray,
+// wi towards where the light comes from, which is where a sampled ray continues.
 pub trait Material {
-    fn scatter(&self, _ray_in: &Ray, _rec: &HitRecord) -> Option<(Ray, Color)> {
+    // Fraction of the light arriving from wi that leaves towards wo, including the
+    // cosine at surfaces. Zero for sharp reflections, only sample can find those.
+    fn eval(&self, _rec: &HitRecord, _wo: Vec3, _wi: Vec3) -> Color {
+        Color::ZERO
+    }
+
+    // Density with respect to solid angle that sample picks wi with
+    fn pdf(&self, _rec: &HitRecord, _wo: Vec3, _wi: Vec3) -> f64 {
+        0.0
+    }
+
+    // New direction from the random numbers uc and u in [0, 1), None when the light is
+    // absorbed
+    fn sample(&self, _rec: &HitRecord, _wo: Vec3, _uc: f64, _u: [f64; 2]) -> Option<ScatterSample> {
         None
     }
+
+    // Light given off by the surface itself
+    fn emitted(&self, _rec: &HitRecord) -> Color {
+        Color::ZERO
+    }
+}
+
+pub struct ScatterSample {
+    pub wi: Vec3,
+    // eval / pdf, by how much the light along wi is scaled
+    pub weight: Color,
+    pub pdf: f64,
+    // mirror reflection or refraction, eval and pdf are zero for wi
+    pub specular: bool,
 }
 
+// Mirror direction of wo in the shading frame
+fn reflect(wo: Vec3) -> Vec3 {
+    Vec3::new(-wo.x, -wo.y, wo.z)
+}
+
+#[derive(Clone)]
 pub struct EmptyMaterial {}
 
 impl Material for EmptyMaterial {}
 
+#[derive(Clone)]
 pub struct Lambertian {
     // albedo = measure of diffuse reflection
-    pub(crate) albedo: Color,
+    pub(crate) albedo: Textures,
 }
 
 impl Lambertian {
     pub fn new(color: &Color) -> Self {
-        Lambertian { albedo: *color }
+        Lambertian::textured(*color)
+    }
+
+    pub fn textured(albedo: impl Into<Textures>) -> Self {
+        Lambertian {
+            albedo: albedo.into(),
+        }
     }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _ray_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
-        let mut scatter_direction = rec.normal + Vec3::random_unit_vector();
+    // albedo / pi times the cosine, sample picks directions with the same density
+    fn eval(&self, rec: &HitRecord, wo: Vec3, wi: Vec3) -> Color {
+        self.pdf(rec, wo, wi) * self.albedo.value(rec.u, rec.v, &rec.p)
+    }
 
-        if scatter_direction.near_zero() {
-            scatter_direction = rec.normal;
-        }
+    fn pdf(&self, _rec: &HitRecord, _wo: Vec3, wi: Vec3) -> f64 {
+        cosine_hemisphere_pdf(wi.z)
+    }
 
-        let scattered = Ray {
-            orig: rec.p,
-            dir: scatter_direction,
-        };
-        let attenuation = self.albedo;
-        Some((scattered, attenuation))
+    fn sample(&self, rec: &HitRecord, wo: Vec3, _uc: f64, u: [f64; 2]) -> Option<ScatterSample> {
+        let wi = cosine_hemisphere(u);
+        Some(ScatterSample {
+            wi,
+            weight: self.albedo.value(rec.u, rec.v, &rec.p),
+            pdf: self.pdf(rec, wo, wi),
+            specular: false,
+        })
     }
 }
 
+#[derive(Clone)]
 pub struct Metal {
-    pub(crate) albedo: Color,
+    pub(crate) albedo: Textures,
     pub fuzziness: f64,
 }
 
 impl Metal {
     pub fn new(albedo: Color, fuzziness: f64) -> Self {
+        Metal::textured(albedo, fuzziness)
+    }
+
+    pub fn textured(albedo: impl Into<Textures>, fuzziness: f64) -> Self {
         // each ray going to a metal is randomized to simulate fuzzing
         let fuzziness = if fuzziness < 1.0 { fuzziness } else { 1.0 };
-        Metal { albedo, fuzziness }
+        Metal {
+            albedo: albedo.into(),
+            fuzziness,
+        }
     }
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
-        let reflected = Vec3::reflect(&Vec3::unit_vector(ray_in.direction()), &rec.normal);
-        let scattered = Ray {
-            orig: rec.p,
-            dir: reflected + self.fuzziness * Vec3::random_in_unit_sphere(),
-        };
-        let attenuation = self.albedo;
-        if dot(scattered.direction(), rec.normal) > 0.0 {
-            Some((scattered, attenuation))
-        } else {
-            None
+    // Directions scattered below the surface are absorbed, the others keep the albedo.
+    // A perfect mirror cannot be evaluated.
+    fn eval(&self, rec: &HitRecord, wo: Vec3, wi: Vec3) -> Color {
+        if self.fuzziness <= 0.0 || wi.z <= 0.0 {
+            return Color::ZERO;
+        }
+        self.pdf(rec, wo, wi) * self.albedo.value(rec.u, rec.v, &rec.p)
+    }
+
+    // The reflected direction is moved by a random point in a ball of radius fuzziness.
+    // Integrating t^2 over the part of the ray inside the ball gives the density.
+    fn pdf(&self, _rec: &HitRecord, wo: Vec3, wi: Vec3) -> f64 {
+        let radius = self.fuzziness;
+        if radius <= 0.0 {
+            return 0.0;
+        }
+
+        let half_b = dot(wi, reflect(wo));
+        let discriminant = half_b * half_b - (1.0 - radius * radius);
+        if discriminant <= 0.0 {
+            return 0.0;
+        }
+        let t_far = half_b + discriminant.sqrt();
+        let t_near = (half_b - discriminant.sqrt()).max(0.0);
+        if t_far <= 0.0 {
+            return 0.0;
+        }
+        (t_far.powi(3) - t_near.powi(3)) / (4.0 * PI * radius.powi(3))
+    }
+
+    fn sample(&self, rec: &HitRecord, wo: Vec3, uc: f64, u: [f64; 2]) -> Option<ScatterSample> {
+        let albedo = self.albedo.value(rec.u, rec.v, &rec.p);
+        if self.fuzziness <= 0.0 {
+            return Some(ScatterSample {
+                wi: reflect(wo),
+                weight: albedo,
+                pdf: 0.0,
+                specular: true,
+            });
+        }
+
+        // The cube root spreads the offsets uniformly over the volume of the ball
+        let offset = uc.cbrt() * uniform_sphere(u);
+        let wi = (reflect(wo) + self.fuzziness * offset).unit_vector();
+        if wi.z <= 0.0 {
+            return None;
         }
+
+        Some(ScatterSample {
+            wi,
+            weight: albedo,
+            pdf: self.pdf(rec, wo, wi),
+            specular: false,
+        })
     }
 }
 
+#[derive(Clone)]
 pub struct Dielectric {
     pub(crate) index_of_refraction: f64,
 }
@@ -108,34 +244,186 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
-        let attenuation = Color::ONE;
-
+    fn sample(&self, rec: &HitRecord, wo: Vec3, uc: f64, _u: [f64; 2]) -> Option<ScatterSample> {
         let refraction_ratio = if rec.front_face {
             1.0 / self.index_of_refraction
         } else {
             self.index_of_refraction
         };
-        let unit_direction = ray_in.direction().unit_vector();
 
-        let cos_theta = 1.0_f64.min(dot(-unit_direction, rec.normal));
+        let cos_theta = wo.z.min(1.0);
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
 
         // Snell's law if no solution exists
-        let direction = if cannot_refract
-            || Dielectric::reflectance(cos_theta, refraction_ratio) > random_double()
-        {
-            Vec3::reflect(&unit_direction, &rec.normal)
+        let wi = if cannot_refract || Dielectric::reflectance(cos_theta, refraction_ratio) > uc {
+            reflect(wo)
         } else {
-            Vec3::refract(&unit_direction, &rec.normal, refraction_ratio)
+            Vec3::refract(&-wo, &Vec3::z(1.0), refraction_ratio)
         };
 
-        let scattered = Ray {
-            orig: rec.p,
-            dir: direction,
-        };
+        Some(ScatterSample {
+            wi,
+            weight: Color::ONE,
+            pdf: 0.0,
+            specular: true,
+        })
+    }
+}
+
+// Area light, absorbs all incoming light and emits its own from the front face only,
+// the side the surface normal points to
+#[derive(Clone)]
+pub struct DiffuseLight {
+    pub(crate) emit: Color,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        DiffuseLight { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        if !rec.front_face {
+            return Color::ZERO;
+        }
+        self.emit
+    }
+}
+
+// Scattering inside a participating medium, the phase function picks the new direction
+#[derive(Clone)]
+pub struct Volumetric {
+    pub(crate) albedo: Textures,
+    pub(crate) phase_function: PhaseFunction,
+}
+
+impl Volumetric {
+    pub fn new(albedo: impl Into<Textures>, phase_function: PhaseFunction) -> Self {
+        Volumetric {
+            albedo: albedo.into(),
+            phase_function,
+        }
+    }
+}
+
+// The phase function takes directions the light travels in, the incoming one is -wo
+impl Material for Volumetric {
+    fn eval(&self, rec: &HitRecord, wo: Vec3, wi: Vec3) -> Color {
+        let phase = self.phase_function.value(-wo, wi);
+        phase * self.albedo.value(rec.u, rec.v, &rec.p)
+    }
+
+    fn pdf(&self, _rec: &HitRecord, wo: Vec3, wi: Vec3) -> f64 {
+        self.phase_function.pdf(-wo, wi)
+    }
+
+    fn sample(&self, rec: &HitRecord, wo: Vec3, _uc: f64, u: [f64; 2]) -> Option<ScatterSample> {
+        let wi = self.phase_function.sample(-wo, u);
+        let pdf = self.pdf(rec, wo, wi);
+        Some(ScatterSample {
+            wi,
+            // The albedo unless the direction was drawn from an approximation of the
+            // phase function
+            weight: self.eval(rec, wo, wi) / pdf,
+            pdf,
+            specular: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3::Onb;
 
-        Some((scattered, attenuation))
+    fn materials() -> Vec<Materials> {
+        let albedo = Color::new(0.8, 0.5, 0.2);
+        vec![
+            Materials::Lambertian(Lambertian::new(&albedo)),
+            Materials::Metal(Metal::new(albedo, 0.3)),
+            Materials::Metal(Metal::new(albedo, 1.0)),
+            Materials::Volumetric(Volumetric::new(albedo, PhaseFunction::Isotropic)),
+            Materials::Volumetric(Volumetric::new(
+                albedo,
+                PhaseFunction::HenyeyGreenstein { g: 0.6 },
+            )),
+        ]
+    }
+
+    fn grid(steps: usize) -> impl Iterator<Item = f64> {
+        (0..steps).map(move |i| (i as f64 + 0.5) / steps as f64)
+    }
+
+    #[test]
+    fn sample_agrees_with_eval_and_pdf() {
+        let rec = HitRecord::EMPTY;
+        let outgoing = [
+            Vec3::z(1.0),
+            Vec3::new(0.6, 0.0, 0.8),
+            Vec3::new(-0.2, 0.9, 0.1).unit_vector(),
+        ];
+        for material in materials() {
+            for wo in outgoing {
+                for uc in grid(3) {
+                    for u0 in grid(5) {
+                        for u1 in grid(5) {
+                            let Some(sample) = material.sample(&rec, wo, uc, [u0, u1]) else {
+                                continue;
+                            };
+                            assert!(!sample.specular);
+
+                            let pdf = material.pdf(&rec, wo, sample.wi);
+                            assert!((pdf - sample.pdf).abs() < 1e-9 * pdf, "{pdf}");
+                            assert!(pdf > 0.0);
+
+                            let expected = material.eval(&rec, wo, sample.wi) / pdf;
+                            assert!(
+                                (sample.weight - expected).length() < 1e-9,
+                                "{} != {}",
+                                sample.weight.x,
+                                expected.x
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fuzzy_metal_pdf_integrates_to_at_most_one() {
+        let rec = HitRecord::EMPTY;
+        let steps = 400;
+        for fuzziness in [0.1, 0.5, 1.0] {
+            let metal = Metal::new(Color::ONE, fuzziness);
+            for wo in [Vec3::z(1.0), Vec3::new(0.8, 0.0, 0.6)] {
+                // Midpoint rule over the cone of directions the fuzz ball covers around
+                // the mirror direction, those below the surface are not counted
+                let cos_max = (1.0 - fuzziness * fuzziness).sqrt();
+                let d_cos = (1.0 - cos_max) / steps as f64;
+                let d_phi = 2.0 * PI / steps as f64;
+                let frame = Onb::from_w(reflect(wo));
+                let mut integral = 0.0;
+                for cos_theta in grid(steps).map(|x| cos_max + x * (1.0 - cos_max)) {
+                    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                    for phi in grid(steps).map(|x| 2.0 * PI * x) {
+                        let local =
+                            Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+                        let wi = frame.to_world(local);
+                        if wi.z > 0.0 {
+                            integral += metal.pdf(&rec, wo, wi) * d_cos * d_phi;
+                        }
+                    }
+                }
+                assert!(integral <= 1.0 + 1e-3, "{fuzziness}: {integral}");
+                // a ball around the normal that stays above the surface is all counted
+                if fuzziness < 0.5 && wo.z == 1.0 {
+                    assert!(integral > 1.0 - 1e-3, "{fuzziness}: {integral}");
+                }
+            }
+        }
     }
 }
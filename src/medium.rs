@@ -0,0 +1,378 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable, Hittables};
+use crate::material::{DiffuseLight, Materials, Volumetric};
+use crate::noise::Noise;
+use crate::phase::PhaseFunction;
+use crate::texture::Textures;
+use crate::utilities::random_double;
+use crate::voxel::VoxelGrid;
+use crate::{Color, Point3, Ray, Vec3};
+
+// Volume of uniform density filling a closed boundary, like smoke or fog.
+// Rays pass through it and scatter after a random distance, the denser the
+// medium the shorter the distance.
+pub struct ConstantMedium {
+    boundary: Box<Hittables>,
+    negative_inverse_density: f64,
+    phase_function: Materials,
+}
+
+impl ConstantMedium {
+    pub fn new(
+        boundary: impl Into<Hittables>,
+        density: f64,
+        albedo: impl Into<Textures>,
+        phase_function: PhaseFunction,
+    ) -> Self {
+        ConstantMedium {
+            boundary: Box::new(boundary.into()),
+            negative_inverse_density: -1.0 / density,
+            phase_function: Materials::Volumetric(Volumetric::new(albedo, phase_function)),
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        // Where the ray enters and leaves the boundary, also when it starts inside
+        let entry = self.boundary.hit(r, f64::NEG_INFINITY, f64::INFINITY)?;
+        let exit = self.boundary.hit(r, entry.t + 0.0001, f64::INFINITY)?;
+
+        let t_entry = entry.t.max(t_min).max(0.0);
+        let t_exit = exit.t.min(t_max);
+        if t_entry >= t_exit {
+            return None;
+        }
+
+        let ray_length = r.direction().length();
+        let distance_inside_boundary = (t_exit - t_entry) * ray_length;
+        let hit_distance = self.negative_inverse_density * random_double().ln();
+        if hit_distance > distance_inside_boundary {
+            return None;
+        }
+
+        let mut rec = HitRecord::EMPTY;
+        rec.t = t_entry + hit_distance / ray_length;
+        rec.p = r.at(rec.t);
+        // The medium has no surface, the normal and the side are arbitrary
+        rec.normal = Vec3::x(1.0);
+        rec.front_face = true;
+        rec.material = &self.phase_function;
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+}
+
+// Where the density of a heterogeneous medium comes from, values are in [0, max_value()]
+pub enum DensityField {
+    // stretched over the bounding box of the medium
+    Grid(VoxelGrid),
+    // fractal noise in world space, thinning out to gaps where it is negative
+    Noise {
+        noise: Noise,
+        scale: f64,
+        octaves: u32,
+    },
+}
+
+impl DensityField {
+    fn max_value(&self) -> f64 {
+        match self {
+            DensityField::Grid(grid) => grid.max_value(),
+            DensityField::Noise { .. } => 1.0,
+        }
+    }
+
+    fn value(&self, p: &Point3, bounds: &Aabb) -> f64 {
+        match self {
+            DensityField::Grid(grid) => {
+                let local = (*p - bounds.minimum) / bounds.diagonal();
+                grid.value(&local)
+            }
+            DensityField::Noise {
+                noise,
+                scale,
+                octaves,
+            } => (2.0 * noise.fbm(&(*scale * *p), *octaves)).clamp(0.0, 1.0),
+        }
+    }
+}
+
+// Medium whose density varies in space. The coefficients give the absorption
+// and scattering per unit length at density 1, absorbed light is replaced by
+// the emission.
+pub struct HeterogeneousMedium {
+    boundary: Box<Hittables>,
+    bounds: Aabb,
+    density: DensityField,
+    absorption: f64,
+    scattering: f64,
+    // upper bound of the extinction anywhere in the medium
+    majorant: f64,
+    phase_function: Materials,
+    emitter: Materials,
+}
+
+impl HeterogeneousMedium {
+    pub fn new(
+        boundary: impl Into<Hittables>,
+        density: DensityField,
+        absorption: f64,
+        scattering: f64,
+        emission: Color,
+        phase_function: PhaseFunction,
+    ) -> Self {
+        let boundary = boundary.into();
+        let bounds = boundary
+            .bounding_box()
+            .unwrap_or(Aabb::new(Point3::ZERO, Point3::ONE));
+        let majorant = density.max_value() * (absorption + scattering);
+
+        HeterogeneousMedium {
+            boundary: Box::new(boundary),
+            bounds,
+            density,
+            absorption,
+            scattering,
+            majorant,
+            phase_function: Materials::Volumetric(Volumetric::new(Color::ONE, phase_function)),
+            emitter: Materials::DiffuseLight(DiffuseLight::new(emission)),
+        }
+    }
+}
+
+impl HeterogeneousMedium {
+    // Part of the ray between t_min and t_max inside the boundary
+    fn interval(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        // The steps between collisions would be zero or NaN and never reach the exit
+        if !(self.majorant > 0.0 && self.majorant.is_finite()) {
+            return None;
+        }
+
+        let entry = self.boundary.hit(r, f64::NEG_INFINITY, f64::INFINITY)?;
+        let exit = self.boundary.hit(r, entry.t + 0.0001, f64::INFINITY)?;
+
+        let t_entry = entry.t.max(t_min).max(0.0);
+        let t_exit = exit.t.min(t_max);
+        (t_entry < t_exit).then_some((t_entry, t_exit))
+    }
+
+    // Delta tracking: collisions are sampled against the majorant as if the medium
+    // was uniform, then each one is absorbed, scattered or passed through with
+    // the probabilities of the real coefficients at that point
+    fn sample_collision(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (t_entry, t_exit) = self.interval(r, t_min, t_max)?;
+
+        let ray_length = r.direction().length();
+        let mut t = t_entry;
+        loop {
+            t -= (1.0 - random_double()).ln() / (self.majorant * ray_length);
+            if t >= t_exit {
+                return None;
+            }
+
+            let p = r.at(t);
+            let density = self.density.value(&p, &self.bounds);
+            let event = random_double() * self.majorant;
+
+            let material = if event < density * self.absorption {
+                &self.emitter
+            } else if event < density * (self.absorption + self.scattering) {
+                &self.phase_function
+            } else {
+                // Null collision, the ray continues unchanged
+                continue;
+            };
+
+            let mut rec = HitRecord::EMPTY;
+            rec.t = t;
+            rec.p = p;
+            rec.normal = Vec3::x(1.0);
+            rec.front_face = true;
+            rec.material = material;
+            return Some(rec);
+        }
+    }
+
+    // Ratio tracking: the same tentative collisions as delta tracking, but instead of
+    // stopping at a real one the fraction of null collisions is multiplied up. Gives
+    // shadow rays a smooth estimate of the light getting through.
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let Some((t_entry, t_exit)) = self.interval(r, t_min, t_max) else {
+            return 1.0;
+        };
+
+        let ray_length = r.direction().length();
+        let extinction = self.absorption + self.scattering;
+        let mut transmittance = 1.0;
+        let mut t = t_entry;
+        loop {
+            t -= (1.0 - random_double()).ln() / (self.majorant * ray_length);
+            if t >= t_exit {
+                return transmittance;
+            }
+
+            let density = self.density.value(&r.at(t), &self.bounds);
+            transmittance *= 1.0 - density * extinction / self.majorant;
+        }
+    }
+}
+
+// Rays are tracked through the medium by the integrator with MediumList, to
+// everything else it is invisible
+impl Hittable for HeterogeneousMedium {
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+}
+
+// Heterogeneous media anywhere in the world except inside instances
+pub struct MediumList<'a> {
+    media: Vec<&'a HeterogeneousMedium>,
+}
+
+impl<'a> MediumList<'a> {
+    pub fn new(world: &'a Hittables) -> Self {
+        let mut media = Vec::new();
+        collect_media(world, &mut media);
+        MediumList { media }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.media.is_empty()
+    }
+
+    // Closest collision of the ray with any of the media before t_max. Each medium
+    // on its own gives a first collision, the nearest of them is distributed like
+    // the first collision in all of them together.
+    pub fn sample_collision(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'a>> {
+        let mut closest = None;
+        let mut t_max = t_max;
+        for medium in &self.media {
+            if let Some(rec) = medium.sample_collision(r, t_min, t_max) {
+                t_max = rec.t;
+                closest = Some(rec);
+            }
+        }
+        closest
+    }
+
+    // Fraction of the light that travels along the ray from t_max back to t_min
+    pub fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        self.media
+            .iter()
+            .map(|medium| medium.transmittance(r, t_min, t_max))
+            .product()
+    }
+}
+
+fn collect_media<'a>(object: &'a Hittables, media: &mut Vec<&'a HeterogeneousMedium>) {
+    match object {
+        Hittables::HeterogeneousMedium(medium) => media.push(medium),
+        Hittables::List(list) => {
+            for object in list.objects() {
+                collect_media(object, media);
+            }
+        }
+        Hittables::Bvh(bvh) => {
+            for object in bvh.objects() {
+                collect_media(object, media);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::EmptyMaterial;
+    use crate::quad::Cuboid;
+    use crate::utilities::seed_random;
+
+    const TRIALS: usize = 20000;
+
+    // Slab between x = 0 and x = thickness
+    fn slab(thickness: f64) -> Cuboid {
+        Cuboid::new(
+            Point3::new(0.0, -5.0, -5.0),
+            Point3::new(thickness, 5.0, 5.0),
+            Materials::EmptyMaterial(EmptyMaterial {}),
+        )
+    }
+
+    fn uniform_medium(thickness: f64, absorption: f64, scattering: f64) -> Hittables {
+        let grid = VoxelGrid::new([1, 1, 1], vec![1.0]).unwrap();
+        HeterogeneousMedium::new(
+            slab(thickness),
+            DensityField::Grid(grid),
+            absorption,
+            scattering,
+            Color::ZERO,
+            PhaseFunction::Isotropic,
+        )
+        .into()
+    }
+
+    // The direction is not normalized, distances along the ray must still be lengths
+    fn across() -> Ray {
+        Ray::new(Point3::new(-1.0, 0.3, 0.2), Vec3::x(2.0), 0.0)
+    }
+
+    fn past() -> Ray {
+        Ray::new(Point3::new(-1.0, 7.0, 0.0), Vec3::x(2.0), 0.0)
+    }
+
+    #[test]
+    fn constant_medium_lets_through_exp_of_the_optical_depth() {
+        seed_random(7);
+        let (density, thickness) = (0.5, 2.0);
+        let medium = ConstantMedium::new(
+            slab(thickness),
+            density,
+            Color::ONE,
+            PhaseFunction::Isotropic,
+        );
+        let passed = (0..TRIALS)
+            .filter(|_| medium.hit(&across(), 0.001, f64::INFINITY).is_none())
+            .count();
+        let fraction = passed as f64 / TRIALS as f64;
+        let expected = (-density * thickness).exp();
+        assert!(
+            (fraction - expected).abs() < 0.015,
+            "{fraction} != {expected}"
+        );
+    }
+
+    #[test]
+    fn transmittance_is_exp_of_the_optical_depth() {
+        seed_random(7);
+        let thickness = 2.0;
+        let world = uniform_medium(thickness, 0.2, 0.3);
+        let media = MediumList::new(&world);
+        let mean = (0..TRIALS)
+            .map(|_| media.transmittance(&across(), 0.001, f64::INFINITY))
+            .sum::<f64>()
+            / TRIALS as f64;
+        let expected = (-0.5 * thickness).exp();
+        assert!((mean - expected).abs() < 0.015, "{mean} != {expected}");
+    }
+
+    #[test]
+    fn rays_missing_the_boundary_are_unaffected() {
+        let medium = ConstantMedium::new(slab(2.0), 100.0, Color::ONE, PhaseFunction::Isotropic);
+        let world = uniform_medium(2.0, 50.0, 50.0);
+        let media = MediumList::new(&world);
+        for _ in 0..100 {
+            assert!(medium.hit(&past(), 0.001, f64::INFINITY).is_none());
+            assert!(media
+                .sample_collision(&past(), 0.001, f64::INFINITY)
+                .is_none());
+            assert_eq!(media.transmittance(&past(), 0.001, f64::INFINITY), 1.0);
+        }
+    }
+}
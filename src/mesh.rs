@@ -0,0 +1,107 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::bvh::Bvh;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Materials;
+use crate::triangle::{intersect_triangle, triangle_bounding_box, triangle_hit_record, Triangle};
+use crate::{Point3, Ray, Vec3};
+
+// Indices into the vertex attribute arrays of a mesh
+#[derive(Copy, Clone)]
+pub struct MeshFace {
+    pub positions: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+    pub material: usize,
+}
+
+// Faces of a g or o statement. Nothing in the renderer picks parts of a model yet,
+// the groups are kept for callers that want to.
+#[allow(dead_code)]
+pub struct MeshGroup {
+    pub name: String,
+    pub faces: Range<usize>,
+}
+
+// Indexed triangle mesh, vertices are shared between faces
+pub struct TriangleMesh {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f64, f64)>,
+    pub faces: Vec<MeshFace>,
+    pub groups: Vec<MeshGroup>,
+    pub materials: Vec<Materials>,
+}
+
+impl TriangleMesh {
+    fn vertices(&self, face: &MeshFace) -> [Point3; 3] {
+        face.positions.map(|i| self.positions[i])
+    }
+}
+
+// A single face of a shared mesh, cheap enough to be stored in a BVH
+pub struct MeshTriangle {
+    mesh: Arc<TriangleMesh>,
+    face: usize,
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let face = &self.mesh.faces[self.face];
+        let vertices = self.mesh.vertices(face);
+        let (t, barycentric) = intersect_triangle(&vertices, ray, t_min, t_max)?;
+
+        let normals = face.normals.map(|n| n.map(|i| self.mesh.normals[i]));
+        let uvs = match face.uvs {
+            Some(uvs) => uvs.map(|i| self.mesh.uvs[i]),
+            None => Triangle::DEFAULT_UVS,
+        };
+
+        Some(triangle_hit_record(
+            ray,
+            t,
+            barycentric,
+            &vertices,
+            normals,
+            &uvs,
+            &self.mesh.materials[face.material],
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let face = &self.mesh.faces[self.face];
+        Some(triangle_bounding_box(&self.mesh.vertices(face)))
+    }
+}
+
+pub struct Mesh {
+    bvh: Bvh<MeshTriangle>,
+}
+
+impl Mesh {
+    pub fn new(data: TriangleMesh) -> Self {
+        let data = Arc::new(data);
+        let triangles = (0..data.faces.len())
+            .map(|face| MeshTriangle {
+                mesh: Arc::clone(&data),
+                face,
+            })
+            .collect();
+
+        Mesh {
+            bvh: Bvh::new(triangles),
+        }
+    }
+}
+
+impl Hittable for Mesh {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.bvh.hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bvh.bounding_box()
+    }
+}
@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Materials, Metal};
+use crate::obj::ObjError;
+use crate::texture::{ImageTexture, Textures, WrapMode};
+use crate::Color;
+
+// Material statement of a .mtl library, fields default to the values Blender assumes
+pub struct MtlMaterial {
+    pub diffuse: Color,
+    // map_Kd image, replaces the diffuse color
+    pub diffuse_texture: Option<Textures>,
+    pub specular: Color,
+    pub emissive: Color,
+    pub specular_exponent: f64,
+    pub index_of_refraction: f64,
+    pub dissolve: f64,
+    pub illumination_model: u32,
+}
+
+impl MtlMaterial {
+    pub const DEFAULT: MtlMaterial = MtlMaterial {
+        diffuse: Color {
+            x: 0.8,
+            y: 0.8,
+            z: 0.8,
+        },
+        diffuse_texture: None,
+        specular: Color::ZERO,
+        emissive: Color::ZERO,
+        specular_exponent: 0.0,
+        index_of_refraction: 1.0,
+        dissolve: 1.0,
+        illumination_model: 2,
+    };
+
+    // Maps the Phong style description onto the closest material of the renderer
+    pub fn to_material(&self) -> Materials {
+        let transparent = self.dissolve < 1.0 || matches!(self.illumination_model, 4 | 6 | 7 | 9);
+        // Exporters write a specular color for plain diffuse surfaces too, so only
+        // the illumination model marks a surface as a mirror
+        let reflective = matches!(self.illumination_model, 3 | 5 | 8);
+
+        if !self.emissive.near_zero() {
+            Materials::DiffuseLight(DiffuseLight::new(self.emissive))
+        } else if transparent {
+            let index_of_refraction = if self.index_of_refraction > 1.0 {
+                self.index_of_refraction
+            } else {
+                1.5
+            };
+            Materials::Dielectric(Dielectric::new(index_of_refraction))
+        } else if reflective {
+            // Blinn-Phong exponent to roughness, 1000 is a mirror and 0 fully rough
+            let fuzziness = (2.0 / (self.specular_exponent + 2.0)).sqrt();
+            // Without a Ks the mirror takes the surface color instead of turning black
+            let albedo = if self.specular.near_zero() {
+                self.diffuse
+            } else {
+                self.specular
+            };
+            Materials::Metal(Metal::new(albedo, fuzziness))
+        } else {
+            match &self.diffuse_texture {
+                Some(texture) => Materials::Lambertian(Lambertian::textured(texture.clone())),
+                None => Materials::Lambertian(Lambertian::new(&self.diffuse)),
+            }
+        }
+    }
+}
+
+pub fn load_mtl(path: &Path) -> Result<HashMap<String, MtlMaterial>, ObjError> {
+    let file = File::open(path).map_err(|error| ObjError::Io {
+        file: path.to_path_buf(),
+        error,
+    })?;
+    parse_mtl(BufReader::new(file), path)
+}
+
+pub fn parse_mtl(
+    reader: impl BufRead,
+    path: &Path,
+) -> Result<HashMap<String, MtlMaterial>, ObjError> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+    // images used by several materials are only decoded once
+    let mut textures: HashMap<PathBuf, Textures> = HashMap::new();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(|error| ObjError::Io {
+            file: path.to_path_buf(),
+            error,
+        })?;
+        let error = |message: String| ObjError::Parse {
+            file: path.to_path_buf(),
+            line: index + 1,
+            message,
+        };
+
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => &line,
+        };
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let arguments = tokens.collect::<Vec<&str>>();
+
+        if keyword == "newmtl" {
+            if arguments.is_empty() {
+                return Err(error(String::from("newmtl needs a material name")));
+            }
+            if let Some((name, material)) = current.take() {
+                materials.insert(name, material);
+            }
+            current = Some((arguments.join(" "), MtlMaterial::DEFAULT));
+            continue;
+        }
+
+        let material = match current.as_mut() {
+            Some((_, material)) => material,
+            None => return Err(error(format!("'{keyword}' before any newmtl statement"))),
+        };
+
+        match keyword {
+            "Kd" => material.diffuse = parse_color(&arguments).map_err(error)?,
+            "Ks" => material.specular = parse_color(&arguments).map_err(error)?,
+            "Ke" => material.emissive = parse_color(&arguments).map_err(error)?,
+            "Ns" => material.specular_exponent = parse_float(&arguments).map_err(error)?,
+            "Ni" => material.index_of_refraction = parse_float(&arguments).map_err(error)?,
+            "d" => material.dissolve = parse_float(&arguments).map_err(error)?,
+            "Tr" => material.dissolve = 1.0 - parse_float(&arguments).map_err(error)?,
+            "map_Kd" => {
+                // Options like -s or -o come before the file name and are not supported
+                let name = arguments
+                    .last()
+                    .ok_or_else(|| error(String::from("map_Kd needs a file name")))?;
+                let file = path.parent().unwrap_or(Path::new("")).join(name);
+                let texture = match textures.get(&file) {
+                    Some(texture) => texture.clone(),
+                    None => {
+                        let texture: Textures = ImageTexture::load(&file, WrapMode::Repeat)
+                            .map_err(|error| ObjError::Texture {
+                                file: file.clone(),
+                                error,
+                            })?
+                            .into();
+                        textures.insert(file, texture.clone());
+                        texture
+                    }
+                };
+                material.diffuse_texture = Some(texture);
+            }
+            "illum" => {
+                material.illumination_model = arguments
+                    .first()
+                    .and_then(|argument| argument.parse().ok())
+                    .ok_or_else(|| error(String::from("illum needs an integer model")))?;
+            }
+            // Ambient colors, the other texture maps and the like have no counterpart
+            _ => {}
+        }
+    }
+    if let Some((name, material)) = current {
+        materials.insert(name, material);
+    }
+
+    Ok(materials)
+}
+
+fn parse_float(arguments: &[&str]) -> Result<f64, String> {
+    match arguments {
+        [value] => value
+            .parse()
+            .map_err(|_| format!("'{value}' is not a number")),
+        _ => Err(format!("expected 1 number, found {}", arguments.len())),
+    }
+}
+
+// A single value is used for all three channels
+fn parse_color(arguments: &[&str]) -> Result<Color, String> {
+    let values = arguments
+        .iter()
+        .map(|value| {
+            value
+                .parse::<f64>()
+                .map_err(|_| format!("'{value}' is not a number"))
+        })
+        .collect::<Result<Vec<f64>, String>>()?;
+
+    match values[..] {
+        [v] => Ok(Color::new(v, v, v)),
+        [r, g, b] => Ok(Color::new(r, g, b)),
+        _ => Err(format!("expected 1 or 3 numbers, found {}", values.len())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::Texture;
+    use crate::Point3;
+
+    fn parse(text: &str) -> HashMap<String, MtlMaterial> {
+        parse_mtl(text.as_bytes(), Path::new("models/test.mtl")).unwrap()
+    }
+
+    fn material(text: &str) -> Materials {
+        parse(&format!("newmtl test\n{text}"))["test"].to_material()
+    }
+
+    #[test]
+    fn illum_3_is_metal() {
+        match material("Ks 0.9 0.9 0.9\nNs 1000\nillum 3\n") {
+            Materials::Metal(metal) => assert!(metal.fuzziness < 0.05, "{}", metal.fuzziness),
+            _ => panic!("expected a metal"),
+        }
+    }
+
+    #[test]
+    fn specular_color_alone_stays_diffuse() {
+        let material = material("Kd 0.5 0.5 0.5\nKs 0.5 0.5 0.5\nNs 100\nillum 2\n");
+        assert!(matches!(material, Materials::Lambertian(_)));
+    }
+
+    #[test]
+    fn mirror_without_specular_color_takes_the_diffuse_color() {
+        for text in [
+            "Kd 0.2 0.4 0.6\nillum 3\n",
+            "Kd 0.2 0.4 0.6\nKs 0 0 0\nillum 5\n",
+        ] {
+            match material(text) {
+                Materials::Metal(metal) => {
+                    let albedo = metal.albedo.value(0.0, 0.0, &Point3::ZERO);
+                    assert_eq!((albedo.x, albedo.y, albedo.z), (0.2, 0.4, 0.6));
+                }
+                _ => panic!("expected a metal"),
+            }
+        }
+    }
+
+    #[test]
+    fn dissolve_is_dielectric() {
+        match material("d 0.5\nNi 1.33\n") {
+            Materials::Dielectric(dielectric) => assert_eq!(dielectric.index_of_refraction, 1.33),
+            _ => panic!("expected a dielectric"),
+        }
+    }
+
+    #[test]
+    fn transparency_is_dielectric_with_default_index() {
+        match material("Tr 0.5\nNi 1.0\n") {
+            Materials::Dielectric(dielectric) => assert_eq!(dielectric.index_of_refraction, 1.5),
+            _ => panic!("expected a dielectric"),
+        }
+    }
+
+    #[test]
+    fn emission_is_diffuse_light() {
+        let material = material("Kd 0.8 0.8 0.8\nKe 4 4 4\n");
+        assert!(matches!(material, Materials::DiffuseLight(_)));
+    }
+
+    #[test]
+    fn zero_emission_stays_lambertian() {
+        let material = material("Kd 0.8 0.8 0.8\nKe 0 0 0\n");
+        assert!(matches!(material, Materials::Lambertian(_)));
+    }
+
+    #[test]
+    fn materials_are_kept_by_name() {
+        let materials = parse("newmtl red\nKd 1 0 0\nnewmtl green\nKd 0 1 0\n");
+        let (red, green) = (materials["red"].diffuse, materials["green"].diffuse);
+        assert_eq!([red.x, red.y, red.z], [1.0, 0.0, 0.0]);
+        assert_eq!([green.x, green.y, green.z], [0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn texture_is_relative_to_the_library() {
+        let text = "newmtl test\nmap_Kd -s 1 1 1 missing.png\n";
+        match parse_mtl(text.as_bytes(), Path::new("models/test.mtl")) {
+            Err(ObjError::Texture { file, .. }) => {
+                assert_eq!(file, Path::new("models/missing.png"))
+            }
+            _ => panic!("expected a texture error"),
+        }
+    }
+
+    #[test]
+    fn error_reports_the_line_number() {
+        let text = "# comment\nnewmtl test\nKd 0.5 0.5 0.5\nNs high\n";
+        match parse_mtl(text.as_bytes(), Path::new("test.mtl")) {
+            Err(ObjError::Parse { line, message, .. }) => {
+                assert_eq!(line, 4);
+                assert_eq!(message, "'high' is not a number");
+            }
+            _ => panic!("expected a parse error"),
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::rng::{mix, Pcg32};
+use crate::{Point3, Vec3};
+
+#[derive(Copy, Clone, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NoiseKind {
+    // Ken Perlin's improved noise on a cubic lattice
+    #[default]
+    Perlin,
+    // Simplex noise, fewer directional artifacts and cheaper in 3D
+    Simplex,
+}
+
+// Gradient noise with values in about [-1, 1], the same seed always gives the same pattern
+#[derive(Clone)]
+pub struct Noise {
+    kind: NoiseKind,
+    // permutation of 0..256 repeated twice, so indices never need wrapping
+    permutation: Arc<[u8; 512]>,
+}
+
+impl Noise {
+    pub fn new(kind: NoiseKind, seed: u64) -> Self {
+        let mut values = [0u8; 256];
+        for (i, value) in values.iter_mut().enumerate() {
+            *value = i as u8;
+        }
+        // Fisher-Yates shuffle
+        let mut rng = Pcg32::new(seed, 0);
+        for i in (1..values.len()).rev() {
+            values.swap(i, rng.next_index(i + 1));
+        }
+
+        let mut permutation = [0u8; 512];
+        for (i, value) in permutation.iter_mut().enumerate() {
+            *value = values[i % 256];
+        }
+
+        Noise {
+            kind,
+            permutation: Arc::new(permutation),
+        }
+    }
+
+    pub fn value(&self, p: &Point3) -> f64 {
+        match self.kind {
+            NoiseKind::Perlin => self.perlin(p),
+            NoiseKind::Simplex => self.simplex(p),
+        }
+    }
+
+    // Fractal Brownian motion, octaves of doubling frequency and halving amplitude
+    pub fn fbm(&self, p: &Point3, octaves: u32) -> f64 {
+        self.octaves(p, octaves, |value| value)
+    }
+
+    // Like fbm with the absolute value of each octave, in [0, 1]
+    pub fn turbulence(&self, p: &Point3, octaves: u32) -> f64 {
+        self.octaves(p, octaves, f64::abs)
+    }
+
+    // Sum of the octaves normalized by the total weight
+    fn octaves(&self, p: &Point3, octaves: u32, shape: impl Fn(f64) -> f64) -> f64 {
+        let mut sum = 0.0;
+        let mut total_weight = 0.0;
+        let mut weight = 1.0;
+        let mut p = *p;
+
+        for _ in 0..octaves.max(1) {
+            sum += weight * shape(self.value(&p));
+            total_weight += weight;
+            weight *= 0.5;
+            p = 2.0 * p;
+        }
+
+        sum / total_weight
+    }
+
+    fn hash(&self, x: i64, y: i64, z: i64) -> usize {
+        let permutation = &self.permutation;
+        let x = (x & 255) as usize;
+        let y = (y & 255) as usize;
+        let z = (z & 255) as usize;
+        permutation[permutation[permutation[x] as usize + y] as usize + z] as usize
+    }
+
+    fn perlin(&self, p: &Point3) -> f64 {
+        let (x0, y0, z0) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (x, y, z) = (p.x - x0, p.y - y0, p.z - z0);
+        let (i, j, k) = (x0 as i64, y0 as i64, z0 as i64);
+
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        // Gradient contributions of the eight cell corners, blended along each axis
+        let corner = |di: i64, dj: i64, dk: i64| {
+            let hash = self.hash(i + di, j + dj, k + dk);
+            gradient(hash, x - di as f64, y - dj as f64, z - dk as f64)
+        };
+
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 0), corner(1, 0, 0)),
+                lerp(u, corner(0, 1, 0), corner(1, 1, 0)),
+            ),
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 1), corner(1, 0, 1)),
+                lerp(u, corner(0, 1, 1), corner(1, 1, 1)),
+            ),
+        )
+    }
+
+    // Stefan Gustavson's formulation of 3D simplex noise
+    fn simplex(&self, p: &Point3) -> f64 {
+        const SKEW: f64 = 1.0 / 3.0;
+        const UNSKEW: f64 = 1.0 / 6.0;
+
+        // Cell of the skewed grid the point is in
+        let s = (p.x + p.y + p.z) * SKEW;
+        let (i, j, k) = (
+            (p.x + s).floor() as i64,
+            (p.y + s).floor() as i64,
+            (p.z + s).floor() as i64,
+        );
+        let t = (i + j + k) as f64 * UNSKEW;
+        let origin = Vec3::new(i as f64 - t, j as f64 - t, k as f64 - t);
+        let d0 = *p - origin;
+
+        // The cube is split into six tetrahedra, find the one containing the point
+        let (first, second) = if d0.x >= d0.y {
+            if d0.y >= d0.z {
+                ((1, 0, 0), (1, 1, 0))
+            } else if d0.x >= d0.z {
+                ((1, 0, 0), (1, 0, 1))
+            } else {
+                ((0, 0, 1), (1, 0, 1))
+            }
+        } else if d0.y < d0.z {
+            ((0, 0, 1), (0, 1, 1))
+        } else if d0.x < d0.z {
+            ((0, 1, 0), (0, 1, 1))
+        } else {
+            ((0, 1, 0), (1, 1, 0))
+        };
+
+        let corners = [(0, 0, 0), first, second, (1, 1, 1)];
+        let mut sum = 0.0;
+        for (n, (di, dj, dk)) in corners.into_iter().enumerate() {
+            let d = d0 - Vec3::new(di as f64, dj as f64, dk as f64) + n as f64 * Vec3::ONE * UNSKEW;
+            let falloff = 0.6 - d.length_squared();
+            if falloff > 0.0 {
+                let hash = self.hash(i + di, j + dj, k + dk);
+                sum += falloff.powi(4) * gradient(hash, d.x, d.y, d.z);
+            }
+        }
+
+        // Scales the result to about [-1, 1]
+        32.0 * sum
+    }
+}
+
+// 6t^5 - 15t^4 + 10t^3, smooth up to the second derivative at the lattice points
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+// Dot product with one of the twelve directions towards the edges of a cube
+fn gradient(hash: usize, x: f64, y: f64, z: f64) -> f64 {
+    match hash & 15 {
+        0 | 12 => x + y,
+        1 | 14 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x + z,
+        5 => -x + z,
+        6 => x - z,
+        7 => -x - z,
+        8 => y + z,
+        9 | 13 => -y + z,
+        10 => y - z,
+        _ => -y - z,
+    }
+}
+
+// Cellular noise, distances to the feature points scattered one per unit cell
+#[derive(Copy, Clone)]
+pub struct Worley {
+    seed: u64,
+}
+
+impl Worley {
+    pub fn new(seed: u64) -> Self {
+        Worley { seed }
+    }
+
+    // Distances to the closest and the second closest feature point
+    pub fn distances(&self, p: &Point3) -> (f64, f64) {
+        let (i, j, k) = (p.x.floor() as i64, p.y.floor() as i64, p.z.floor() as i64);
+        let mut closest = f64::INFINITY;
+        let mut second = f64::INFINITY;
+
+        for di in -1..=1 {
+            for dj in -1..=1 {
+                for dk in -1..=1 {
+                    let cell = (i + di, j + dj, k + dk);
+                    let distance = (self.feature_point(cell) - *p).length();
+                    if distance < closest {
+                        second = closest;
+                        closest = distance;
+                    } else if distance < second {
+                        second = distance;
+                    }
+                }
+            }
+        }
+
+        (closest, second)
+    }
+
+    fn feature_point(&self, (i, j, k): (i64, i64, i64)) -> Point3 {
+        // The SplitMix64 increment keeps the all zero input from hashing to zero
+        let mut hash = self.seed;
+        for coordinate in [i, j, k] {
+            hash = mix((hash ^ coordinate as u64).wrapping_add(0x9e37_79b9_7f4a_7c15));
+        }
+
+        // 21 bits of the hash for each coordinate within the cell
+        let offset = |shift: u32| ((hash >> shift) & 0x1f_ffff) as f64 / (1 << 21) as f64;
+        Point3::new(
+            i as f64 + offset(0),
+            j as f64 + offset(21),
+            k as f64 + offset(42),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Scattered points with fractional and negative coordinates
+    fn points() -> impl Iterator<Item = Point3> {
+        (0..4000).map(|i| {
+            let i = i as f64;
+            Point3::new(
+                (i * 0.7548776662).fract() * 40.0 - 20.0,
+                (i * 0.5698402910).fract() * 40.0 - 20.0,
+                (i * 0.3247179572).fract() * 40.0 - 20.0,
+            )
+        })
+    }
+
+    // Gradient noise of the given kind, Worley noise for None
+    fn values(kind: Option<NoiseKind>, seed: u64) -> Vec<f64> {
+        match kind {
+            Some(kind) => {
+                let noise = Noise::new(kind, seed);
+                points().map(|p| noise.value(&p)).collect()
+            }
+            None => {
+                let worley = Worley::new(seed);
+                points().map(|p| worley.distances(&p).0).collect()
+            }
+        }
+    }
+
+    const KINDS: [Option<NoiseKind>; 3] = [Some(NoiseKind::Perlin), Some(NoiseKind::Simplex), None];
+
+    #[test]
+    fn same_seed_gives_the_same_values() {
+        for kind in KINDS {
+            assert_eq!(values(kind, 42), values(kind, 42));
+        }
+    }
+
+    #[test]
+    fn different_seeds_give_different_values() {
+        for kind in KINDS {
+            let first = values(kind, 42);
+            let second = values(kind, 43);
+            let differing = first.iter().zip(&second).filter(|(a, b)| a != b).count();
+            assert!(differing > first.len() / 2, "{differing}");
+        }
+    }
+
+    #[test]
+    fn values_stay_in_range() {
+        for kind in [NoiseKind::Perlin, NoiseKind::Simplex] {
+            let noise = Noise::new(kind, 5);
+            for p in points() {
+                for value in [noise.value(&p), noise.fbm(&p, 5)] {
+                    assert!((-1.0..=1.0).contains(&value), "{value}");
+                }
+                let turbulence = noise.turbulence(&p, 5);
+                assert!((0.0..=1.0).contains(&turbulence), "{turbulence}");
+            }
+        }
+
+        // The feature point of the cell around p is at most a cell diagonal away
+        let worley = Worley::new(5);
+        for p in points() {
+            let (closest, second) = worley.distances(&p);
+            assert!(0.0 <= closest && closest <= second, "{closest} {second}");
+            assert!(closest <= 3.0_f64.sqrt(), "{closest}");
+        }
+    }
+}
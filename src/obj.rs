@@ -0,0 +1,384 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use crate::material::Materials;
+use crate::mesh::{Mesh, MeshFace, MeshGroup, TriangleMesh};
+use crate::mtl::{load_mtl, MtlMaterial};
+use crate::{Point3, Vec3};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        file: PathBuf,
+        error: std::io::Error,
+    },
+    Parse {
+        file: PathBuf,
+        line: usize,
+        message: String,
+    },
+    Texture {
+        file: PathBuf,
+        error: image::ImageError,
+    },
+}
+
+impl Display for ObjError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ObjError::Io { file, error } => write!(f, "{}: {error}", file.display()),
+            ObjError::Parse {
+                file,
+                line,
+                message,
+            } => write!(f, "{}:{line}: {message}", file.display()),
+            ObjError::Texture { file, error } => write!(f, "{}: {error}", file.display()),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+// One corner of a face, indices are already resolved to zero based
+#[derive(Copy, Clone)]
+struct FaceVertex {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+// Loads a Wavefront OBJ file. Faces pick their material with usemtl from the
+// libraries named by mtllib, the given material is used for all other faces and
+// for names the libraries don't define.
+pub fn load_obj(path: &Path, material: Materials) -> Result<Mesh, ObjError> {
+    let file = File::open(path).map_err(|error| ObjError::Io {
+        file: path.to_path_buf(),
+        error,
+    })?;
+    let mesh = parse_obj(BufReader::new(file), path, material)?;
+    Ok(Mesh::new(mesh))
+}
+
+// The path is used in error messages and to find material libraries
+pub fn parse_obj(
+    reader: impl BufRead,
+    path: &Path,
+    material: Materials,
+) -> Result<TriangleMesh, ObjError> {
+    let mut mesh = TriangleMesh {
+        positions: Vec::new(),
+        normals: Vec::new(),
+        uvs: Vec::new(),
+        faces: Vec::new(),
+        groups: Vec::new(),
+        materials: vec![material],
+    };
+    let mut group_name = String::from("default");
+    let mut group_start = 0;
+    let mut library: HashMap<String, MtlMaterial> = HashMap::new();
+    // usemtl names already converted, mapped to their index in mesh.materials
+    let mut used_materials: HashMap<String, usize> = HashMap::new();
+    let mut current_material = 0;
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(|error| ObjError::Io {
+            file: path.to_path_buf(),
+            error,
+        })?;
+        let error = |message: String| ObjError::Parse {
+            file: path.to_path_buf(),
+            line: index + 1,
+            message,
+        };
+
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => &line,
+        };
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let arguments = tokens.collect::<Vec<&str>>();
+
+        match keyword {
+            "v" => {
+                let [x, y, z] = parse_floats(&arguments, 3, 4).map_err(error)?;
+                mesh.positions.push(Point3::new(x, y, z));
+            }
+            "vn" => {
+                let [x, y, z] = parse_floats(&arguments, 3, 3).map_err(error)?;
+                let normal = Vec3::new(x, y, z);
+                if normal.length_squared() == 0.0 {
+                    return Err(error("normal has zero length".to_string()));
+                }
+                mesh.normals.push(normal.unit_vector());
+            }
+            "vt" => {
+                let [u, v, _] = parse_floats(&arguments, 1, 3).map_err(error)?;
+                mesh.uvs.push((u, v));
+            }
+            "f" => {
+                if arguments.len() < 3 {
+                    return Err(error(format!(
+                        "face needs at least 3 vertices, found {}",
+                        arguments.len()
+                    )));
+                }
+                let corners = arguments
+                    .iter()
+                    .map(|corner| parse_face_vertex(corner, &mesh))
+                    .collect::<Result<Vec<FaceVertex>, String>>()
+                    .map_err(error)?;
+
+                let has_uvs = corners.iter().all(|c| c.uv.is_some());
+                let has_normals = corners.iter().all(|c| c.normal.is_some());
+
+                // Polygons are split into a fan around the first corner
+                for i in 1..corners.len() - 1 {
+                    let triangle = [corners[0], corners[i], corners[i + 1]];
+                    mesh.faces.push(MeshFace {
+                        positions: triangle.map(|c| c.position),
+                        uvs: has_uvs.then(|| triangle.map(|c| c.uv.unwrap())),
+                        normals: has_normals.then(|| triangle.map(|c| c.normal.unwrap())),
+                        material: current_material,
+                    });
+                }
+            }
+            "g" | "o" => {
+                close_group(&mut mesh, &group_name, group_start);
+                group_start = mesh.faces.len();
+                group_name = if arguments.is_empty() {
+                    String::from("default")
+                } else {
+                    arguments.join(" ")
+                };
+            }
+            "mtllib" => {
+                if arguments.is_empty() {
+                    return Err(error(String::from("mtllib needs a file name")));
+                }
+                let directory = path.parent().unwrap_or(Path::new(""));
+                for name in &arguments {
+                    library.extend(load_mtl(&directory.join(name))?);
+                }
+            }
+            "usemtl" => {
+                let name = arguments.join(" ");
+                current_material = match used_materials.get(&name) {
+                    Some(&index) => index,
+                    None => match library.get(&name) {
+                        Some(definition) => {
+                            mesh.materials.push(definition.to_material());
+                            used_materials.insert(name, mesh.materials.len() - 1);
+                            mesh.materials.len() - 1
+                        }
+                        None => 0,
+                    },
+                };
+            }
+            // Smoothing groups, lines, points and free-form geometry are not rendered
+            _ => {}
+        }
+    }
+    close_group(&mut mesh, &group_name, group_start);
+
+    Ok(mesh)
+}
+
+fn close_group(mesh: &mut TriangleMesh, name: &str, start: usize) {
+    if mesh.faces.len() > start {
+        mesh.groups.push(MeshGroup {
+            name: name.to_string(),
+            faces: start..mesh.faces.len(),
+        });
+    }
+}
+
+// Parses between min and max numbers, only the first 3 are kept and missing ones
+// are zero
+fn parse_floats(arguments: &[&str], min: usize, max: usize) -> Result<[f64; 3], String> {
+    if arguments.len() < min || arguments.len() > max {
+        return Err(format!(
+            "expected {min} to {max} numbers, found {}",
+            arguments.len()
+        ));
+    }
+    let mut values = [0.0; 3];
+    for (i, argument) in arguments.iter().enumerate() {
+        let value: f64 = argument
+            .parse()
+            .map_err(|_| format!("'{argument}' is not a number"))?;
+        if !value.is_finite() {
+            return Err(format!("'{argument}' is not finite"));
+        }
+        if let Some(slot) = values.get_mut(i) {
+            *slot = value;
+        }
+    }
+    Ok(values)
+}
+
+// Accepts v, v/vt, v//vn and v/vt/vn
+fn parse_face_vertex(corner: &str, mesh: &TriangleMesh) -> Result<FaceVertex, String> {
+    let mut parts = corner.split('/');
+    let position = resolve_index(parts.next(), mesh.positions.len(), "vertex")?
+        .ok_or_else(|| format!("face vertex '{corner}' has no position index"))?;
+    let uv = resolve_index(parts.next(), mesh.uvs.len(), "texture coordinate")?;
+    let normal = resolve_index(parts.next(), mesh.normals.len(), "normal")?;
+    if parts.next().is_some() {
+        return Err(format!("face vertex '{corner}' has too many indices"));
+    }
+
+    Ok(FaceVertex {
+        position,
+        uv,
+        normal,
+    })
+}
+
+// OBJ indices start at 1, negative ones count back from the last element
+fn resolve_index(index: Option<&str>, count: usize, kind: &str) -> Result<Option<usize>, String> {
+    let index = match index {
+        Some(index) if !index.is_empty() => index,
+        _ => return Ok(None),
+    };
+    let value: i64 = index
+        .parse()
+        .map_err(|_| format!("'{index}' is not a valid {kind} index"))?;
+
+    let resolved = if value > 0 {
+        value - 1
+    } else {
+        count as i64 + value
+    };
+    if value == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!(
+            "{kind} index {value} is out of range, {count} defined so far"
+        ));
+    }
+    Ok(Some(resolved as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::EmptyMaterial;
+
+    const SQUARE: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvn 0 0 1\n";
+
+    fn parse(text: &str) -> Result<TriangleMesh, ObjError> {
+        let material = Materials::EmptyMaterial(EmptyMaterial {});
+        parse_obj(text.as_bytes(), Path::new("test.obj"), material)
+    }
+
+    // Line number and message of a parse error
+    fn parse_error(text: &str) -> (usize, String) {
+        match parse(text) {
+            Err(ObjError::Parse { line, message, .. }) => (line, message),
+            Err(error) => panic!("expected a parse error, got {error}"),
+            Ok(_) => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn quad_is_split_into_a_fan() {
+        let mesh = parse(&format!("{SQUARE}f 1 2 3 4\n")).unwrap();
+        let faces = mesh
+            .faces
+            .iter()
+            .map(|face| face.positions)
+            .collect::<Vec<_>>();
+        assert_eq!(faces, vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn position_and_normal_without_uv() {
+        let mesh = parse(&format!("{SQUARE}f 1//1 2//1 3//1\n")).unwrap();
+        let face = &mesh.faces[0];
+        assert_eq!(face.normals, Some([0, 0, 0]));
+        assert_eq!(face.uvs, None);
+    }
+
+    #[test]
+    fn negative_indices_count_back() {
+        let mesh = parse(&format!("{SQUARE}f -3 -2 -1\n")).unwrap();
+        assert_eq!(mesh.faces[0].positions, [1, 2, 3]);
+    }
+
+    #[test]
+    fn index_zero_is_rejected() {
+        let (line, message) = parse_error(&format!("{SQUARE}f 0 1 2\n"));
+        assert_eq!(line, 6);
+        assert!(message.contains("vertex index 0"), "{message}");
+    }
+
+    #[test]
+    fn negative_index_out_of_range_is_rejected() {
+        let (line, message) = parse_error(&format!("{SQUARE}f -5 1 2\n"));
+        assert_eq!(line, 6);
+        assert!(
+            message.contains("vertex index -5 is out of range"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn too_many_slashes_are_rejected() {
+        let (_, message) = parse_error(&format!("{SQUARE}vt 0 0\nf 1/1/1/1 2 3\n"));
+        assert!(message.contains("too many indices"), "{message}");
+    }
+
+    #[test]
+    fn error_reports_the_line_number() {
+        let (line, message) = parse_error("# comment\n\nv 0 0 0\nv 1 x 0\n");
+        assert_eq!(line, 4);
+        assert_eq!(message, "'x' is not a number");
+    }
+
+    #[test]
+    fn non_finite_values_are_rejected() {
+        for line in ["v 0 nan 0", "vn inf 0 1", "vt 0 -inf"] {
+            let (number, message) = parse_error(&format!("v 0 0 0\n{line}\n"));
+            assert_eq!(number, 2);
+            assert!(message.contains("is not finite"), "{message}");
+        }
+    }
+
+    #[test]
+    fn zero_length_normal_is_rejected() {
+        let (line, message) = parse_error(&format!("{SQUARE}vn 0 0 0\n"));
+        assert_eq!(line, 6);
+        assert_eq!(message, "normal has zero length");
+    }
+
+    #[test]
+    fn faces_are_grouped() {
+        let text = format!("{SQUARE}f 1 2 3\ng top\nf 1 3 4\nf 1 2 4\n");
+        let mesh = parse(&text).unwrap();
+        let groups = mesh
+            .groups
+            .iter()
+            .map(|group| (group.name.as_str(), group.faces.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(groups, vec![("default", 0..1), ("top", 1..3)]);
+    }
+
+    #[test]
+    fn every_coordinate_is_checked() {
+        let (line, message) = parse_error("v 0 0 0\nv 1 2 3 abc\n");
+        assert_eq!(line, 2);
+        assert_eq!(message, "'abc' is not a number");
+    }
+
+    #[test]
+    fn undefined_material_uses_the_default() {
+        let mesh = parse(&format!("{SQUARE}usemtl missing\nf 1 2 3\n")).unwrap();
+        assert_eq!(mesh.materials.len(), 1);
+        assert_eq!(mesh.faces[0].material, 0);
+    }
+}
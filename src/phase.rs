@@ -0,0 +1,112 @@
+use std::f64::consts::PI;
+
+use crate::sampling::{uniform_sphere, uniform_sphere_pdf};
+use crate::vec3::{dot, Onb};
+use crate::Vec3;
+
+// Angular distribution of the light a medium scatters. Both directions point the
+// way the light travels, so a positive g scatters forward.
+#[derive(Copy, Clone)]
+pub enum PhaseFunction {
+    Isotropic,
+    // g in (-1, 1) is the average cosine of the scattering angle
+    HenyeyGreenstein { g: f64 },
+}
+
+// Close to isotropic the inverted distribution loses precision, there directions
+// are sampled uniformly instead
+const NEARLY_ISOTROPIC: f64 = 1e-3;
+
+impl PhaseFunction {
+    // Phase functions integrate to one over the sphere
+    pub fn value(&self, direction_in: Vec3, direction_out: Vec3) -> f64 {
+        match *self {
+            PhaseFunction::Isotropic => uniform_sphere_pdf(),
+            PhaseFunction::HenyeyGreenstein { g } => {
+                let cos_theta = dot(direction_in.unit_vector(), direction_out.unit_vector());
+                henyey_greenstein(g, cos_theta)
+            }
+        }
+    }
+
+    // Density sample picks direction_out with, equal to the value unless nearly isotropic
+    pub fn pdf(&self, direction_in: Vec3, direction_out: Vec3) -> f64 {
+        match *self {
+            PhaseFunction::HenyeyGreenstein { g } if g.abs() >= NEARLY_ISOTROPIC => {
+                self.value(direction_in, direction_out)
+            }
+            _ => uniform_sphere_pdf(),
+        }
+    }
+
+    // Unit scattered direction from the random numbers u in [0, 1)
+    pub fn sample(&self, direction_in: Vec3, u: [f64; 2]) -> Vec3 {
+        let g = match *self {
+            PhaseFunction::HenyeyGreenstein { g } if g.abs() >= NEARLY_ISOTROPIC => g,
+            _ => return uniform_sphere(u),
+        };
+
+        // Inverted cumulative distribution of the cosine
+        let s = (1.0 - g * g) / (1.0 + g - 2.0 * g * u[0]);
+        let cos_theta = (1.0 + g * g - s * s) / (2.0 * g);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * u[1];
+
+        let local = Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        Onb::from_w(direction_in).to_world(local)
+    }
+}
+
+fn henyey_greenstein(g: f64, cos_theta: f64) -> f64 {
+    let denominator = 1.0 + g * g - 2.0 * g * cos_theta;
+    (1.0 - g * g) / (4.0 * PI * denominator * denominator.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phase_functions() -> [(f64, PhaseFunction); 3] {
+        [-0.7, 0.0, 0.7].map(|g| (g, PhaseFunction::HenyeyGreenstein { g }))
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        let direction_in = Vec3::new(1.0, 2.0, -1.0);
+        let frame = Onb::from_w(direction_in);
+        for (g, phase) in phase_functions() {
+            // The pdf only depends on the cosine of the scattering angle
+            let steps = 20000;
+            let integral: f64 = (0..steps)
+                .map(|i| {
+                    let cos_theta = 2.0 * (i as f64 + 0.5) / steps as f64 - 1.0;
+                    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                    let direction_out = frame.to_world(Vec3::new(sin_theta, 0.0, cos_theta));
+                    2.0 * PI * phase.pdf(direction_in, direction_out) * 2.0 / steps as f64
+                })
+                .sum();
+            assert!((integral - 1.0).abs() < 1e-4, "g = {g}: {integral}");
+        }
+    }
+
+    #[test]
+    fn mean_cosine_of_samples_is_g() {
+        let direction_in = Vec3::new(1.0, 2.0, -1.0);
+        for (g, phase) in phase_functions() {
+            // Stratified over both random numbers, isotropic scattering uses the
+            // second one for more than the azimuth
+            let steps = 200;
+            let mean = (0..steps * steps)
+                .map(|i| {
+                    let u = [
+                        ((i % steps) as f64 + 0.5) / steps as f64,
+                        ((i / steps) as f64 + 0.5) / steps as f64,
+                    ];
+                    dot(direction_in.unit_vector(), phase.sample(direction_in, u))
+                })
+                .sum::<f64>()
+                / (steps * steps) as f64;
+            assert!((mean - g).abs() < 1e-3, "g = {g}: {mean}");
+        }
+    }
+}
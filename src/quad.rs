@@ -0,0 +1,254 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Materials;
+use crate::vec3::dot;
+use crate::{Point3, Ray, Vec3};
+
+// Parallelogram spanned by the edges u and v from the corner q
+pub struct Quad {
+    pub q: Point3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub material: Materials,
+    normal: Vec3,
+    // plane equation dot(normal, p) = d
+    d: f64,
+    // projects a point in the plane onto the (u, v) coordinates
+    w: Vec3,
+    area: f64,
+}
+
+impl Quad {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, material: Materials) -> Self {
+        let n = Vec3::cross(u, v);
+        let normal = n.unit_vector();
+        Quad {
+            q,
+            u,
+            v,
+            material,
+            normal,
+            d: dot(normal, q),
+            w: n / dot(n, n),
+            area: n.length(),
+        }
+    }
+
+    // Unit direction towards a uniformly chosen point of the quad, the pdf is the area
+    // density converted to solid angle as seen from origin
+    pub fn sample_direction(&self, origin: Point3, u: [f64; 2]) -> (Vec3, f64) {
+        let point = self.q + u[0] * self.u + u[1] * self.v;
+        let direction = (point - origin).unit_vector();
+        (direction, self.direction_pdf(origin, point))
+    }
+
+    // Solid angle pdf of sample_direction for the direction from origin to a point
+    // of the quad
+    pub fn direction_pdf(&self, origin: Point3, point: Point3) -> f64 {
+        let to_point = point - origin;
+        let cosine = dot(to_point.unit_vector(), self.normal).abs();
+        to_point.length_squared() / (cosine * self.area)
+    }
+
+    // Axis-aligned rectangles, the normal points along the positive axis
+
+    pub fn xy_rect(x: (f64, f64), y: (f64, f64), z: f64, material: Materials) -> Self {
+        Quad::new(
+            Point3::new(x.0, y.0, z),
+            Vec3::x(x.1 - x.0),
+            Vec3::y(y.1 - y.0),
+            material,
+        )
+    }
+
+    pub fn xz_rect(x: (f64, f64), z: (f64, f64), y: f64, material: Materials) -> Self {
+        Quad::new(
+            Point3::new(x.0, y, z.0),
+            Vec3::z(z.1 - z.0),
+            Vec3::x(x.1 - x.0),
+            material,
+        )
+    }
+
+    pub fn yz_rect(y: (f64, f64), z: (f64, f64), x: f64, material: Materials) -> Self {
+        Quad::new(
+            Point3::new(x, y.0, z.0),
+            Vec3::y(y.1 - y.0),
+            Vec3::z(z.1 - z.0),
+            material,
+        )
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let denominator = dot(self.normal, ray.direction());
+
+        // Ray is parallel to the plane
+        if denominator.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = (self.d - dot(self.normal, ray.origin())) / denominator;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let p = ray.at(t);
+        let planar_hit = p - self.q;
+        let alpha = dot(self.w, Vec3::cross(planar_hit, self.v));
+        let beta = dot(self.w, Vec3::cross(self.u, planar_hit));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        let mut rec = HitRecord::EMPTY;
+        rec.t = t;
+        rec.p = p;
+        rec.u = alpha;
+        rec.v = beta;
+        rec.set_face_normal(ray, &self.normal);
+        rec.material = &self.material;
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let bounds = Aabb::new(self.q, self.q + self.u + self.v);
+        let bounds = Aabb::surrounding_box(&bounds, &Aabb::new(self.q + self.u, self.q + self.v));
+        Some(bounds.padded(1e-4))
+    }
+}
+
+// Axis-aligned box made of six quads facing outwards
+pub struct Cuboid {
+    sides: Vec<Quad>,
+    bounds: Aabb,
+}
+
+impl Cuboid {
+    // a and b are opposite corners, they must differ along every axis or some of
+    // the sides are degenerate quads without a normal
+    pub fn new(a: Point3, b: Point3, material: Materials) -> Self {
+        let bounds = Aabb::new(a, b);
+        let (min, max) = (bounds.minimum, bounds.maximum);
+        let d = bounds.diagonal();
+        debug_assert!(
+            d.x > 0.0 && d.y > 0.0 && d.z > 0.0,
+            "box corners must differ along every axis"
+        );
+
+        let dx = Vec3::x(d.x);
+        let dy = Vec3::y(d.y);
+        let dz = Vec3::z(d.z);
+
+        let sides = vec![
+            Quad::new(Point3::new(min.x, min.y, max.z), dx, dy, material.clone()), // front
+            Quad::new(Point3::new(max.x, min.y, max.z), -dz, dy, material.clone()), // right
+            Quad::new(Point3::new(max.x, min.y, min.z), -dx, dy, material.clone()), // back
+            Quad::new(Point3::new(min.x, min.y, min.z), dz, dy, material.clone()), // left
+            Quad::new(Point3::new(min.x, max.y, max.z), dx, -dz, material.clone()), // top
+            Quad::new(Point3::new(min.x, min.y, min.z), dx, dz, material),         // bottom
+        ];
+
+        Cuboid { sides, bounds }
+    }
+
+    pub fn sides(&self) -> &[Quad] {
+        &self.sides
+    }
+}
+
+impl Hittable for Cuboid {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut hit_anything = None;
+        let mut closest_so_far = t_max;
+
+        for side in &self.sides {
+            if let Some(rec) = side.hit(ray, t_min, closest_so_far) {
+                hit_anything = Some(rec);
+                closest_so_far = rec.t;
+            }
+        }
+
+        hit_anything
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds.padded(1e-4))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::EmptyMaterial;
+
+    fn empty() -> Materials {
+        Materials::EmptyMaterial(EmptyMaterial {})
+    }
+
+    // Two by one rectangle in the plane z = 1, facing +z
+    fn rectangle() -> Quad {
+        Quad::new(
+            Point3::new(1.0, 1.0, 1.0),
+            Vec3::x(2.0),
+            Vec3::y(1.0),
+            empty(),
+        )
+    }
+
+    fn down_at(x: f64, y: f64) -> Ray {
+        Ray::new(Point3::new(x, y, 3.0), Vec3::z(-1.0), 0.0)
+    }
+
+    #[test]
+    fn hit_has_the_position_along_the_edges_as_uv() {
+        let quad = rectangle();
+        let rec = quad.hit(&down_at(2.5, 1.25), 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-12);
+        assert!((rec.p - Point3::new(2.5, 1.25, 1.0)).length() < 1e-12);
+        assert!((rec.u - 0.75).abs() < 1e-12 && (rec.v - 0.25).abs() < 1e-12);
+        assert!(rec.front_face);
+        assert!((rec.normal - Vec3::z(1.0)).length() < 1e-12);
+
+        let rec = quad.hit(&down_at(1.0, 1.0), 0.001, f64::INFINITY).unwrap();
+        assert!(rec.u.abs() < 1e-12 && rec.v.abs() < 1e-12);
+    }
+
+    #[test]
+    fn rays_outside_of_the_edges_or_range_miss() {
+        let quad = rectangle();
+        assert!(quad.hit(&down_at(0.9, 1.5), 0.001, f64::INFINITY).is_none());
+        assert!(quad.hit(&down_at(2.0, 2.1), 0.001, f64::INFINITY).is_none());
+        assert!(quad.hit(&down_at(2.0, 1.5), 0.001, 1.5).is_none());
+
+        let parallel = Ray::new(Point3::new(0.0, 1.5, 1.0), Vec3::x(1.0), 0.0);
+        assert!(quad.hit(&parallel, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn hit_from_behind_is_a_back_face() {
+        let ray = Ray::new(Point3::new(2.0, 1.5, -1.0), Vec3::z(1.0), 0.0);
+        let quad = rectangle();
+        let rec = quad.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!(!rec.front_face);
+        assert!((rec.normal - Vec3::z(-1.0)).length() < 1e-12);
+    }
+
+    #[test]
+    fn box_sides_face_outwards() {
+        let cuboid = Cuboid::new(Point3::ZERO, Point3::new(1.0, 2.0, 3.0), empty());
+        let center = Point3::new(0.5, 1.0, 1.5);
+        for side in cuboid.sides() {
+            let side_center = side.q + 0.5 * side.u + 0.5 * side.v;
+            assert!(dot(side.normal, side_center - center) > 0.0);
+        }
+
+        // The closest side is hit from outside
+        let ray = Ray::new(Point3::new(0.5, 1.0, 10.0), Vec3::z(-1.0), 0.0);
+        let rec = cuboid.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 7.0).abs() < 1e-12);
+        assert!(rec.front_face);
+    }
+}
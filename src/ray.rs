@@ -3,11 +3,13 @@ use crate::{Point3, Vec3};
 pub struct Ray {
     pub orig: Point3,
     pub dir: Vec3,
+    // moment within the frame the ray was sent at, for motion blur
+    pub time: f64,
 }
 
 impl Ray {
-    pub fn new(orig: Point3, dir: Vec3) -> Self {
-        Ray { orig, dir }
+    pub fn new(orig: Point3, dir: Vec3, time: f64) -> Self {
+        Ray { orig, dir, time }
     }
 
     pub(crate) fn origin(&self) -> Vec3 {
@@ -18,6 +20,10 @@ impl Ray {
         self.dir
     }
 
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
     pub fn at(&self, t: f64) -> Vec3 {
         self.orig + self.dir * t
     }
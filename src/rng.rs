@@ -0,0 +1,70 @@
+// PCG32 (O'Neill, XSH-RR variant), small and fast with a fixed output for every
+// seed, so images can be reproduced on any machine and thread count
+#[derive(Clone)]
+pub struct Pcg32 {
+    state: u64,
+    // odd, selects one of 2^63 different sequences
+    increment: u64,
+}
+
+const MULTIPLIER: u64 = 6364136223846793005;
+
+impl Pcg32 {
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Pcg32 {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    // Sequence of one sample of one pixel. Neighbouring pixels and samples get
+    // unrelated sequences, the hashes spread their indices over all the bits.
+    pub fn for_sample(seed: u64, pixel: u64, sample: u64) -> Self {
+        let stream = mix(seed ^ mix(pixel));
+        Pcg32::new(mix(stream ^ mix(sample)), stream)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(MULTIPLIER).wrapping_add(self.increment);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    // Uniform in [0, 1)
+    pub fn next_double(&mut self) -> f64 {
+        self.next_u32() as f64 / 4294967296.0
+    }
+
+    // Uniform in 0..bound, Lemire's multiply and shift, the bias is below 2^-32 * bound
+    pub fn next_index(&mut self, bound: usize) -> usize {
+        ((self.next_u32() as u64 * bound as u64) >> 32) as usize
+    }
+}
+
+// Finalizer of SplitMix64, every bit of the input affects every bit of the output
+pub fn mix(x: u64) -> u64 {
+    let x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    let x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_the_reference_implementation() {
+        // First outputs of pcg32-demo from the PCG C library, seed 42 and sequence 54
+        let mut rng = Pcg32::new(42, 54);
+        let outputs: Vec<u32> = (0..6).map(|_| rng.next_u32()).collect();
+        assert_eq!(
+            outputs,
+            [0xa15c02b7, 0x7b47f409, 0xba1d3330, 0x83d2f293, 0xbfa4784b, 0xcbed606e]
+        );
+    }
+}
@@ -0,0 +1,452 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::rng::mix;
+use crate::utilities::random_double;
+
+#[derive(Copy, Clone, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SamplerKind {
+    // Uniform random numbers
+    Independent,
+    // One random point in each stratum of a grid per dimension
+    Stratified,
+    // Halton sequence with Owen scrambled digits
+    Halton,
+    // Owen scrambled Sobol points, best with a power of two samples per pixel
+    Sobol,
+}
+
+const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+
+// Random numbers in [0, 1) for the samples of one pixel. Each sample asks for the
+// same dimensions in the same order, the first two place it in the pixel. The
+// quasi-random samplers spread the values of one dimension evenly over the
+// samples of a pixel, independent random numbers fill in where they can't.
+pub trait Sampler {
+    fn start_pixel_sample(&mut self, pixel: u64, index: u32);
+
+    fn get_1d(&mut self) -> f64;
+
+    fn get_2d(&mut self) -> [f64; 2];
+}
+
+#[derive(Clone)]
+pub enum Samplers {
+    Independent(IndependentSampler),
+    Stratified(StratifiedSampler),
+    Halton(HaltonSampler),
+    Sobol(SobolSampler),
+}
+
+impl Samplers {
+    pub fn new(kind: SamplerKind, samples_per_pixel: u32, seed: u64) -> Self {
+        let state = SampleState::new(seed);
+        match kind {
+            SamplerKind::Independent => Samplers::Independent(IndependentSampler {}),
+            SamplerKind::Stratified => Samplers::Stratified(StratifiedSampler {
+                samples_per_pixel,
+                state,
+            }),
+            SamplerKind::Halton => Samplers::Halton(HaltonSampler { state }),
+            SamplerKind::Sobol => Samplers::Sobol(SobolSampler { state }),
+        }
+    }
+}
+
+impl Sampler for Samplers {
+    fn start_pixel_sample(&mut self, pixel: u64, index: u32) {
+        match self {
+            Samplers::Independent(sampler) => sampler.start_pixel_sample(pixel, index),
+            Samplers::Stratified(sampler) => sampler.start_pixel_sample(pixel, index),
+            Samplers::Halton(sampler) => sampler.start_pixel_sample(pixel, index),
+            Samplers::Sobol(sampler) => sampler.start_pixel_sample(pixel, index),
+        }
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        match self {
+            Samplers::Independent(sampler) => sampler.get_1d(),
+            Samplers::Stratified(sampler) => sampler.get_1d(),
+            Samplers::Halton(sampler) => sampler.get_1d(),
+            Samplers::Sobol(sampler) => sampler.get_1d(),
+        }
+    }
+
+    fn get_2d(&mut self) -> [f64; 2] {
+        match self {
+            Samplers::Independent(sampler) => sampler.get_2d(),
+            Samplers::Stratified(sampler) => sampler.get_2d(),
+            Samplers::Halton(sampler) => sampler.get_2d(),
+            Samplers::Sobol(sampler) => sampler.get_2d(),
+        }
+    }
+}
+
+// Position in the sequence of the current pixel sample
+#[derive(Clone)]
+struct SampleState {
+    seed: u64,
+    // scrambles the points differently in every pixel
+    pixel_seed: u64,
+    index: u32,
+    dimension: u32,
+}
+
+impl SampleState {
+    fn new(seed: u64) -> Self {
+        SampleState {
+            seed,
+            pixel_seed: 0,
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn start(&mut self, pixel: u64, index: u32) {
+        self.pixel_seed = mix(self.seed ^ mix(pixel));
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    // Hash of the next dimension, which it then moves past
+    fn next_dimension(&mut self) -> (u32, u64) {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        (dimension, mix(self.pixel_seed ^ dimension as u64))
+    }
+}
+
+// Draws from the random numbers of the rendering thread, which are restarted for
+// every pixel sample
+#[derive(Clone)]
+pub struct IndependentSampler {}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, _pixel: u64, _index: u32) {}
+
+    fn get_1d(&mut self) -> f64 {
+        random_double()
+    }
+
+    fn get_2d(&mut self) -> [f64; 2] {
+        [random_double(), random_double()]
+    }
+}
+
+// Every dimension is split into as many strata as there are samples, a random
+// permutation gives each sample its own stratum. Two dimensions use a grid of
+// about square cells. Adaptive sampling goes on in batches of samples_per_pixel,
+// each batch is stratified on its own with another permutation.
+#[derive(Clone)]
+pub struct StratifiedSampler {
+    samples_per_pixel: u32,
+    state: SampleState,
+}
+
+impl StratifiedSampler {
+    // Stratum out of count for the current sample in the next dimension
+    fn next_stratum(&mut self, count: u32) -> u32 {
+        let (_, hash) = self.state.next_dimension();
+        let batch = self.state.index / self.samples_per_pixel;
+        let index = self.state.index % self.samples_per_pixel;
+        permutation_element(index, count, mix(hash ^ batch as u64) as u32)
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, pixel: u64, index: u32) {
+        self.state.start(pixel, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let count = self.samples_per_pixel;
+        let stratum = self.next_stratum(count);
+        ((stratum as f64 + random_double()) / count as f64).min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> [f64; 2] {
+        let columns = (self.samples_per_pixel as f64).sqrt().ceil() as u32;
+        let rows = self.samples_per_pixel.div_ceil(columns);
+        // Extra cells stay empty, each sample is still equally likely in any cell
+        let stratum = self.next_stratum(columns * rows);
+        let x = (stratum % columns) as f64 + random_double();
+        let y = (stratum / columns) as f64 + random_double();
+        [
+            (x / columns as f64).min(ONE_MINUS_EPSILON),
+            (y / rows as f64).min(ONE_MINUS_EPSILON),
+        ]
+    }
+}
+
+// Dimension d uses the radical inverse in the d-th prime base. High bases need
+// many samples to cover the dimension evenly, past the table dimensions are random.
+#[derive(Clone)]
+pub struct HaltonSampler {
+    state: SampleState,
+}
+
+const PRIME_COUNT: usize = 256;
+const PRIMES: [u64; PRIME_COUNT] = first_primes();
+
+const fn first_primes() -> [u64; PRIME_COUNT] {
+    let mut primes = [0; PRIME_COUNT];
+    let mut count = 0;
+    let mut candidate = 2;
+    while count < PRIME_COUNT {
+        let mut i = 0;
+        while i < count && candidate % primes[i] != 0 {
+            i += 1;
+        }
+        if i == count {
+            primes[count] = candidate;
+            count += 1;
+        }
+        candidate += 1;
+    }
+    primes
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, pixel: u64, index: u32) {
+        self.state.start(pixel, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let (dimension, hash) = self.state.next_dimension();
+        match PRIMES.get(dimension as usize) {
+            Some(&base) => owen_scrambled_radical_inverse(base, self.state.index as u64, hash),
+            None => random_double(),
+        }
+    }
+
+    fn get_2d(&mut self) -> [f64; 2] {
+        [self.get_1d(), self.get_1d()]
+    }
+}
+
+// Digits of index in the given base mirrored around the decimal point. Each digit
+// is permuted depending on the digits before it, which keeps the points as
+// evenly spread as without scrambling.
+fn owen_scrambled_radical_inverse(base: u64, mut index: u64, hash: u64) -> f64 {
+    let inverse_base = 1.0 / base as f64;
+    let mut reversed = 0;
+    let mut scale = 1.0;
+    // Leading zero digits are permuted too, 32 bits of precision are enough
+    while scale > 1.0 / 4294967296.0 {
+        let digit = index % base;
+        index /= base;
+        let digit_hash = mix(hash ^ reversed) as u32;
+        reversed =
+            reversed * base + permutation_element(digit as u32, base as u32, digit_hash) as u64;
+        scale *= inverse_base;
+    }
+    (reversed as f64 * scale).min(ONE_MINUS_EPSILON)
+}
+
+// The first two dimensions of the Sobol sequence, which are exact (0, 2) sequences,
+// with the sample order shuffled and the points scrambled differently for every
+// dimension (Burley, Practical Hash-based Owen Scrambling)
+#[derive(Clone)]
+pub struct SobolSampler {
+    state: SampleState,
+}
+
+impl SobolSampler {
+    fn sobol_point(&mut self) -> (u32, u32, u64) {
+        let (_, hash) = self.state.next_dimension();
+        let index = nested_uniform_scramble(self.state.index, hash as u32);
+        (sobol_first(index), sobol_second(index), hash)
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, pixel: u64, index: u32) {
+        self.state.start(pixel, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let (x, _, hash) = self.sobol_point();
+        to_unit(nested_uniform_scramble(x, mix(hash ^ 1) as u32))
+    }
+
+    fn get_2d(&mut self) -> [f64; 2] {
+        let (x, y, hash) = self.sobol_point();
+        [
+            to_unit(nested_uniform_scramble(x, mix(hash ^ 1) as u32)),
+            to_unit(nested_uniform_scramble(y, mix(hash ^ 2) as u32)),
+        ]
+    }
+}
+
+// Van der Corput sequence in base 2
+fn sobol_first(index: u32) -> u32 {
+    index.reverse_bits()
+}
+
+// Its generator matrix is Pascal's triangle modulo 2
+fn sobol_second(mut index: u32) -> u32 {
+    let mut result = 0;
+    let mut direction = 1 << 31;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+    result
+}
+
+// Owen scrambling of the bits of x, the most significant one first
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+// Hash in which each bit only depends on the bits below it
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x ^= x.wrapping_mul(0x3d20adea);
+    x = x.wrapping_add(seed);
+    x = x.wrapping_mul((seed >> 16) | 1);
+    x ^= x.wrapping_mul(0x05526c56);
+    x ^= x.wrapping_mul(0x53a22864);
+    x
+}
+
+fn to_unit(x: u32) -> f64 {
+    x as f64 / 4294967296.0
+}
+
+// Element i of a random permutation of 0..length chosen by the seed, without
+// storing the permutation (Kensler, Correlated Multi-Jittered Sampling)
+fn permutation_element(mut i: u32, length: u32, seed: u32) -> u32 {
+    let mut mask = length.wrapping_sub(1);
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+
+    // Permutes 0..=mask and repeats until the result lands inside 0..length
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= seed >> 16;
+        i ^= (i & mask) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= seed >> 23;
+        i ^= (i & mask) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & mask) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & mask) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & mask) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= mask;
+        i ^= i >> 5;
+        if i < length {
+            break;
+        }
+    }
+    i.wrapping_add(seed) % length
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sobol_points_are_stratified() {
+        for k in 0..8 {
+            let count = 1 << k;
+            let mut sampler = Samplers::new(SamplerKind::Sobol, count, 3);
+            // strata hit per dimension, the first four 2D requests of a sample
+            let mut hit = vec![vec![false; count as usize]; 8];
+            for index in 0..count {
+                sampler.start_pixel_sample(17, index);
+                for pair in 0..4 {
+                    let [x, y] = sampler.get_2d();
+                    for (dimension, value) in [(2 * pair, x), (2 * pair + 1, y)] {
+                        let stratum = (value * count as f64) as usize;
+                        assert!(
+                            !hit[dimension][stratum],
+                            "{count} samples, dimension {dimension}"
+                        );
+                        hit[dimension][stratum] = true;
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stratified_batches_are_stratified() {
+        let count = 6;
+        let mut sampler = Samplers::new(SamplerKind::Stratified, count, 3);
+        // three batches, as adaptive sampling would take them
+        for batch in 0..3 {
+            let mut hit = vec![false; count as usize];
+            for index in batch * count..(batch + 1) * count {
+                sampler.start_pixel_sample(17, index);
+                let stratum = (sampler.get_1d() * count as f64) as usize;
+                assert!(!hit[stratum], "batch {batch}");
+                hit[stratum] = true;
+            }
+        }
+    }
+
+    #[test]
+    fn permutation_element_is_a_bijection() {
+        for length in [1, 2, 3, 5, 7, 8, 13, 16, 100, 257] {
+            for seed in [0, 1, 0xdeadbeef] {
+                let mut seen = vec![false; length as usize];
+                for i in 0..length {
+                    let element = permutation_element(i, length, seed) as usize;
+                    assert!(!seen[element], "length {length}, seed {seed}");
+                    seen[element] = true;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn values_are_in_the_unit_interval() {
+        let kinds = [
+            SamplerKind::Independent,
+            SamplerKind::Stratified,
+            SamplerKind::Halton,
+            SamplerKind::Sobol,
+        ];
+        for kind in kinds {
+            let mut sampler = Samplers::new(kind, 10, 5);
+            for pixel in 0..20 {
+                for index in 0..10 {
+                    sampler.start_pixel_sample(pixel, index);
+                    // far enough to use the random dimensions past the Halton primes
+                    for _ in 0..150 {
+                        let [x, y] = sampler.get_2d();
+                        let z = sampler.get_1d();
+                        for value in [x, y, z] {
+                            assert!((0.0..1.0).contains(&value), "{value}");
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn radical_inverse_is_below_one() {
+        for base in [2, 3, 5, 1619] {
+            for index in [0, 1, 2, u32::MAX as u64] {
+                for hash in [0, u64::MAX, 12345] {
+                    let value = owen_scrambled_radical_inverse(base, index, hash);
+                    assert!((0.0..1.0).contains(&value), "{value}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+use crate::Vec3;
+
+// Warps of random numbers u in [0, 1) to points and directions. Directions are in
+// a local frame around +z, an Onb places them around any other axis.
+
+// Point in the unit disk in the xy plane. Shirley and Chiu's mapping keeps
+// neighbouring u close together, which suits stratified samples.
+pub fn concentric_disk(u: [f64; 2]) -> Vec3 {
+    let x = 2.0 * u[0] - 1.0;
+    let y = 2.0 * u[1] - 1.0;
+    if x == 0.0 && y == 0.0 {
+        return Vec3::ZERO;
+    }
+
+    let (radius, theta) = if x.abs() > y.abs() {
+        (x, FRAC_PI_4 * (y / x))
+    } else {
+        (y, FRAC_PI_2 - FRAC_PI_4 * (x / y))
+    };
+    Vec3::new(radius * theta.cos(), radius * theta.sin(), 0.0)
+}
+
+// Density proportional to the cosine with +z, points of the disk projected up
+// onto the hemisphere (Malley's method)
+pub fn cosine_hemisphere(u: [f64; 2]) -> Vec3 {
+    let d = concentric_disk(u);
+    let z = (1.0 - d.x * d.x - d.y * d.y).max(0.0).sqrt();
+    Vec3::new(d.x, d.y, z)
+}
+
+pub fn cosine_hemisphere_pdf(cos_theta: f64) -> f64 {
+    cos_theta.max(0.0) / PI
+}
+
+pub fn uniform_sphere(u: [f64; 2]) -> Vec3 {
+    let z = 1.0 - 2.0 * u[0];
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u[1];
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+pub fn uniform_sphere_pdf() -> f64 {
+    1.0 / (4.0 * PI)
+}
+
+// Directions within the angle whose cosine is cos_theta_max of +z
+pub fn uniform_cone(u: [f64; 2], cos_theta_max: f64) -> Vec3 {
+    let cos_theta = 1.0 + u[0] * (cos_theta_max - 1.0);
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * u[1];
+    Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+pub fn uniform_cone_pdf(cos_theta_max: f64) -> f64 {
+    1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Corners of a grid including the edges of [0, 1]
+    fn grid(steps: usize) -> impl Iterator<Item = [f64; 2]> {
+        let values = move || (0..steps).map(move |i| i as f64 / (steps - 1) as f64);
+        values().flat_map(move |a| values().map(move |b| [a, b]))
+    }
+
+    // Centers of the cells of a steps x steps grid, a stratified stand-in for
+    // random numbers
+    fn midpoints(steps: usize) -> impl Iterator<Item = [f64; 2]> {
+        let values = move || (0..steps).map(move |i| (i as f64 + 0.5) / steps as f64);
+        values().flat_map(move |a| values().map(move |b| [a, b]))
+    }
+
+    #[test]
+    fn directions_are_unit_vectors() {
+        for u in grid(11) {
+            for direction in [
+                cosine_hemisphere(u),
+                uniform_sphere(u),
+                uniform_cone(u, 0.3),
+            ] {
+                assert!((direction.length() - 1.0).abs() < 1e-12, "{u:?}");
+            }
+            assert!(cosine_hemisphere(u).z >= 0.0);
+            assert!(uniform_cone(u, 0.3).z >= 0.3 - 1e-12);
+            assert!(concentric_disk(u).length() <= 1.0 + 1e-12);
+        }
+    }
+
+    #[test]
+    fn pdfs_match_their_definitions() {
+        assert!((uniform_sphere_pdf() * 4.0 * PI - 1.0).abs() < 1e-12);
+        assert_eq!(cosine_hemisphere_pdf(1.0), 1.0 / PI);
+        assert_eq!(cosine_hemisphere_pdf(0.5), 0.5 / PI);
+        assert_eq!(cosine_hemisphere_pdf(-0.5), 0.0);
+        // a cone of cos_theta_max 0 is the hemisphere
+        assert!((uniform_cone_pdf(0.0) - 1.0 / (2.0 * PI)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn cosine_hemisphere_follows_its_pdf() {
+        // Mean of the cosine under the density cos / pi is 2 / 3, a uniform
+        // hemisphere would give 1 / 2
+        let mean = midpoints(200).map(|u| cosine_hemisphere(u).z).sum::<f64>() / 40000.0;
+        assert!((mean - 2.0 / 3.0).abs() < 1e-3, "{mean}");
+    }
+
+    #[test]
+    fn uniform_sphere_covers_both_halves_evenly() {
+        let (mut z_sum, mut z2_sum) = (0.0, 0.0);
+        for u in midpoints(200) {
+            let z = uniform_sphere(u).z;
+            z_sum += z;
+            z2_sum += z * z;
+        }
+        let count = 40000.0;
+        // mean of z is 0 and of z^2 is 1 / 3 under the density 1 / (4 pi)
+        assert!((z_sum / count).abs() < 1e-3);
+        assert!((z2_sum / count - 1.0 / 3.0).abs() < 1e-3);
+    }
+}
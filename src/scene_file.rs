@@ -0,0 +1,1404 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_path_to_error::Segment;
+use toml::{Table, Value};
+
+use crate::background::Background;
+use crate::camera::Camera;
+use crate::hittable::Hittables;
+use crate::hittable_list::HittableList;
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Materials, Metal};
+use crate::medium::{ConstantMedium, DensityField, HeterogeneousMedium, MediumList};
+use crate::mtl::MtlMaterial;
+use crate::noise::{Noise, NoiseKind};
+use crate::obj::{load_obj, ObjError};
+use crate::phase::PhaseFunction;
+use crate::quad::{Cuboid, Quad};
+use crate::sampler::SamplerKind;
+use crate::scenes::Scene;
+use crate::settings::{OutputFormat, RenderSettings};
+use crate::sphere::{MovingSphere, Sphere};
+use crate::texture::{
+    CheckerTexture, ImageTexture, NoisePattern, NoiseTexture, Textures, WrapMode,
+};
+use crate::tile::TileOrder;
+use crate::transform::{Instance, Matrix4, Motion, MotionStep, Transform};
+use crate::triangle::Triangle;
+use crate::voxel::{VoxelError, VoxelGrid};
+use crate::Vec3;
+
+// Scene files are TOML documents, see scenes/*.toml for examples.
+// Materials and objects select their kind with a `type` key, they are kept as
+// tables at first so errors inside them can name the exact key.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDescription {
+    #[serde(default)]
+    render: RenderDescription,
+    camera: CameraDescription,
+    background: Option<Table>,
+    #[serde(default)]
+    textures: HashMap<String, Table>,
+    #[serde(default)]
+    materials: HashMap<String, Table>,
+    // objects that are only placed in the world through instances
+    #[serde(default)]
+    prototypes: HashMap<String, Table>,
+    #[serde(default)]
+    objects: Vec<Table>,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RenderDescription {
+    width: u32,
+    aspect_ratio: f64,
+    samples_per_pixel: u32,
+    adaptive_threshold: Option<f64>,
+    max_samples_per_pixel: Option<u32>,
+    max_depth: i32,
+    roulette_depth: i32,
+    seed: u64,
+    sampler: SamplerKind,
+    tile_size: u32,
+    tile_order: TileOrder,
+    save_interval: Option<f64>,
+    output: String,
+    format: OutputFormat,
+}
+
+impl Default for RenderDescription {
+    fn default() -> Self {
+        let settings = RenderSettings::default();
+        RenderDescription {
+            width: settings.image_width,
+            aspect_ratio: settings.aspect_ratio,
+            samples_per_pixel: settings.samples_per_pixel,
+            adaptive_threshold: settings.adaptive_threshold,
+            max_samples_per_pixel: settings.max_samples_per_pixel,
+            max_depth: settings.max_depth,
+            roulette_depth: settings.roulette_depth,
+            seed: settings.seed,
+            sampler: settings.sampler,
+            tile_size: settings.tile_size,
+            tile_order: settings.tile_order,
+            save_interval: settings.save_interval,
+            output: settings.output,
+            format: settings.format,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDescription {
+    look_from: [f64; 3],
+    look_at: [f64; 3],
+    #[serde(default = "default_view_up")]
+    view_up: [f64; 3],
+    vertical_fov: f64,
+    #[serde(default)]
+    aperture: f64,
+    // defaults to the distance between look_from and look_at
+    focus_distance: Option<f64>,
+    // part of the frame time from 0 to 1 the image is exposed for
+    #[serde(default = "default_shutter")]
+    shutter: [f64; 2],
+}
+
+fn default_view_up() -> [f64; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+fn default_shutter() -> [f64; 2] {
+    [0.0, 1.0]
+}
+
+// Colors can be given inline or by the name of an entry in [textures]
+#[derive(Deserialize)]
+#[serde(
+    untagged,
+    expecting = "expected a color [r, g, b] or the name of a texture"
+)]
+enum ColorOrTexture {
+    Color([f64; 3]),
+    Texture(String),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDescription {
+    Solid {
+        color: [f64; 3],
+    },
+    // 3D checker pattern with cubes of the given size
+    Checker {
+        scale: f64,
+        even: ColorOrTexture,
+        odd: ColorOrTexture,
+    },
+    // Relative paths are resolved against the directory of the scene file
+    Image {
+        file: PathBuf,
+        #[serde(default)]
+        wrap: WrapMode,
+    },
+    // Procedural pattern blending from the low to the high color
+    Noise {
+        pattern: NoisePattern,
+        #[serde(default)]
+        noise: NoiseKind,
+        #[serde(default)]
+        seed: u64,
+        #[serde(default = "default_noise_scale")]
+        scale: f64,
+        octaves: Option<u32>,
+        distortion: Option<f64>,
+        #[serde(default)]
+        low: [f64; 3],
+        #[serde(default = "default_noise_high")]
+        high: [f64; 3],
+    },
+}
+
+fn default_noise_scale() -> f64 {
+    1.0
+}
+
+fn default_noise_high() -> [f64; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+impl TextureDescription {
+    // Other textures this one is made of, with the key naming them
+    fn references(&self) -> Vec<(&'static str, &str)> {
+        let mut references = Vec::new();
+        if let TextureDescription::Checker { even, odd, .. } = self {
+            for (key, value) in [("even", even), ("odd", odd)] {
+                if let ColorOrTexture::Texture(name) = value {
+                    references.push((key, name.as_str()));
+                }
+            }
+        }
+        references
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDescription {
+    Lambertian {
+        albedo: ColorOrTexture,
+    },
+    Metal {
+        albedo: ColorOrTexture,
+        #[serde(default)]
+        fuzziness: f64,
+    },
+    Dielectric {
+        index_of_refraction: f64,
+    },
+    DiffuseLight {
+        emit: [f64; 3],
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum BackgroundDescription {
+    Solid { color: [f64; 3] },
+    Gradient { horizon: [f64; 3], zenith: [f64; 3] },
+    Black {},
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum ObjectDescription {
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    // Moves from center0 at time 0 to center1 at time 1
+    MovingSphere {
+        center0: [f64; 3],
+        center1: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    Triangle {
+        vertices: [[f64; 3]; 3],
+        normals: Option<[[f64; 3]; 3]>,
+        uvs: Option<[[f64; 2]; 3]>,
+        material: String,
+    },
+    // Parallelogram with corner q and edges u and v
+    Quad {
+        q: [f64; 3],
+        u: [f64; 3],
+        v: [f64; 3],
+        material: String,
+    },
+    // Axis-aligned box between two opposite corners
+    Box {
+        min: [f64; 3],
+        max: [f64; 3],
+        material: String,
+    },
+    // Relative paths are resolved against the directory of the scene file
+    Mesh {
+        file: PathBuf,
+        // used for faces without a usemtl material
+        material: Option<String>,
+    },
+    List {
+        objects: Vec<Table>,
+    },
+    // Smoke or fog of uniform density filling a closed boundary object
+    ConstantMedium {
+        boundary: Table,
+        density: f64,
+        albedo: ColorOrTexture,
+        phase: Option<Table>,
+    },
+    // Medium with a varying density, the coefficients apply at density 1
+    HeterogeneousMedium {
+        boundary: Table,
+        density: Table,
+        absorption: f64,
+        scattering: f64,
+        #[serde(default)]
+        emission: [f64; 3],
+        phase: Option<Table>,
+    },
+    // Places a prototype with transforms applied in the listed order. The motion
+    // steps move the prototype in its own space during the frame, before the
+    // transforms are applied.
+    Instance {
+        object: String,
+        #[serde(default)]
+        transforms: Vec<TransformDescription>,
+        #[serde(default)]
+        motion: Vec<TransformDescription>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum TransformDescription {
+    Translate([f64; 3]),
+    Scale([f64; 3]),
+    Rotate { axis: [f64; 3], angle: f64 },
+    // Row-major 4x4 matrix
+    Matrix([[f64; 4]; 4]),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum DensityDescription {
+    // Voxel file stretched over the bounding box of the boundary, .raw files
+    // need the resolution
+    Grid {
+        file: PathBuf,
+        resolution: Option<[usize; 3]>,
+    },
+    Noise {
+        #[serde(default)]
+        noise: NoiseKind,
+        #[serde(default)]
+        seed: u64,
+        #[serde(default = "default_noise_scale")]
+        scale: f64,
+        #[serde(default = "default_density_octaves")]
+        octaves: u32,
+    },
+}
+
+// Defaults to isotropic when a medium has no phase table
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum PhaseDescription {
+    Isotropic,
+    // g between -1 (backward) and 1 (forward scattering)
+    HenyeyGreenstein { g: f64 },
+}
+
+fn default_density_octaves() -> u32 {
+    5
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        file: PathBuf,
+        error: std::io::Error,
+    },
+    Parse {
+        file: PathBuf,
+        key: String,
+        message: String,
+    },
+    Invalid {
+        file: PathBuf,
+        key: String,
+        message: String,
+    },
+    Obj {
+        key: String,
+        error: ObjError,
+    },
+    Image {
+        key: String,
+        file: PathBuf,
+        error: image::ImageError,
+    },
+    Voxel {
+        key: String,
+        error: VoxelError,
+    },
+}
+
+impl Display for SceneError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SceneError::Io { file, error } => write!(f, "{}: {error}", file.display()),
+            SceneError::Parse { file, key, message } if key.is_empty() => {
+                write!(f, "{}: {message}", file.display())
+            }
+            SceneError::Parse { file, key, message }
+            | SceneError::Invalid { file, key, message } => {
+                write!(f, "{}: `{key}`: {message}", file.display())
+            }
+            SceneError::Obj { key, error } => write!(f, "`{key}`: {error}"),
+            SceneError::Image { key, file, error } => {
+                write!(f, "`{key}`: {}: {error}", file.display())
+            }
+            SceneError::Voxel { key, error } => write!(f, "`{key}`: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+// The overrides are applied to the render settings before the camera is set up
+pub fn load_scene_file(
+    path: &Path,
+    overrides: impl FnOnce(&mut RenderSettings),
+) -> Result<(Scene, RenderSettings), SceneError> {
+    let text = std::fs::read_to_string(path).map_err(|error| SceneError::Io {
+        file: path.to_path_buf(),
+        error,
+    })?;
+    parse_scene(&text, path, overrides)
+}
+
+// The path is used in error messages and to find the files the scene refers to
+pub(crate) fn parse_scene(
+    text: &str,
+    path: &Path,
+    overrides: impl FnOnce(&mut RenderSettings),
+) -> Result<(Scene, RenderSettings), SceneError> {
+    let deserializer = toml::Deserializer::new(text);
+    let description: SceneDescription =
+        serde_path_to_error::deserialize(deserializer).map_err(|error| SceneError::Parse {
+            file: path.to_path_buf(),
+            key: format_key("", error.path()),
+            message: error.inner().to_string().trim().to_string(),
+        })?;
+
+    let mut builder = SceneBuilder {
+        file: path,
+        directory: path.parent().unwrap_or(Path::new("")),
+        textures: HashMap::new(),
+        materials: HashMap::new(),
+        prototypes: HashMap::new(),
+    };
+
+    let mut textures = Vec::new();
+    for (name, table) in description.textures {
+        let texture: TextureDescription =
+            builder.parse_tagged(table, &format!("textures.{name}"))?;
+        textures.push((name, texture));
+    }
+    builder.build_textures(textures)?;
+
+    for (name, table) in description.materials {
+        let key = format!("materials.{name}");
+        let material = builder.parse_tagged(table, &key)?;
+        let material = builder.build_material(material, &key)?;
+        builder.materials.insert(name, material);
+    }
+
+    // Prototypes are built before any of them is registered, so they cannot
+    // instance each other
+    let mut prototypes = HashMap::new();
+    for (name, table) in description.prototypes {
+        let key = format!("prototypes.{name}");
+        let object = builder.build_object(table, &key)?;
+        // The integrator only finds heterogeneous media placed in the world directly
+        if !MediumList::new(&object).is_empty() {
+            return Err(builder.invalid(&key, "heterogeneous media cannot be instanced"));
+        }
+        prototypes.insert(name, Arc::new(object));
+    }
+    builder.prototypes = prototypes;
+
+    let render = description.render;
+    let mut settings = RenderSettings {
+        image_width: render.width,
+        aspect_ratio: render.aspect_ratio,
+        samples_per_pixel: render.samples_per_pixel,
+        adaptive_threshold: render.adaptive_threshold,
+        max_samples_per_pixel: render.max_samples_per_pixel,
+        max_depth: render.max_depth,
+        roulette_depth: render.roulette_depth,
+        seed: render.seed,
+        sampler: render.sampler,
+        tile_size: render.tile_size,
+        tile_order: render.tile_order,
+        save_interval: render.save_interval,
+        output: String::new(),
+        format: render.format,
+    };
+    settings.set_output(&render.output);
+    overrides(&mut settings);
+    settings
+        .validate()
+        .map_err(|message| builder.invalid("render", &message))?;
+
+    let camera = description.camera;
+    let look_from = builder.vector(camera.look_from, "camera.look_from")?;
+    let look_at = builder.vector(camera.look_at, "camera.look_at")?;
+    let view_up = builder.vector(camera.view_up, "camera.view_up")?;
+    if (look_from - look_at).length_squared() == 0.0 {
+        return Err(builder.invalid("camera.look_at", "must differ from look_from"));
+    }
+    // Without a sideways direction the camera has no orientation
+    if Vec3::cross(view_up, look_from - look_at).length_squared() == 0.0 {
+        return Err(builder.invalid(
+            "camera.view_up",
+            "must not be zero or parallel to the view direction",
+        ));
+    }
+    if !(camera.vertical_fov > 0.0 && camera.vertical_fov < 180.0) {
+        return Err(builder.invalid("camera.vertical_fov", "must be between 0 and 180 degrees"));
+    }
+    if !(camera.aperture.is_finite() && camera.aperture >= 0.0) {
+        return Err(builder.invalid("camera.aperture", "must be finite and not negative"));
+    }
+    let focus_distance = match camera.focus_distance {
+        Some(distance) => builder.positive(distance, "camera.focus_distance")?,
+        None => (look_from - look_at).length(),
+    };
+    let [shutter_open, shutter_close] = camera.shutter;
+    if !(shutter_open.is_finite() && shutter_close.is_finite()) {
+        return Err(builder.invalid("camera.shutter", "must be finite"));
+    }
+    if shutter_close < shutter_open {
+        return Err(builder.invalid("camera.shutter", "the shutter cannot close before it opens"));
+    }
+    let camera = Camera::new(
+        look_from,
+        look_at,
+        view_up,
+        camera.vertical_fov,
+        settings.aspect_ratio,
+        camera.aperture,
+        focus_distance,
+    )
+    .shutter(shutter_open, shutter_close);
+
+    let mut world = HittableList::EMPTY;
+    for (i, object) in description.objects.into_iter().enumerate() {
+        world.add(builder.build_object(object, &format!("objects[{i}]"))?);
+    }
+
+    // The sky gradient lights scenes that do not choose a background
+    let background = match description.background {
+        Some(table) => match builder.parse_tagged(table, "background")? {
+            BackgroundDescription::Solid { color } => Background::Solid(Vec3::from(color)),
+            BackgroundDescription::Gradient { horizon, zenith } => Background::Gradient {
+                horizon: Vec3::from(horizon),
+                zenith: Vec3::from(zenith),
+            },
+            BackgroundDescription::Black {} => Background::BLACK,
+        },
+        None => Background::SKY,
+    };
+
+    Ok((
+        Scene {
+            world,
+            camera,
+            background,
+        },
+        settings,
+    ))
+}
+
+// Renders a serde path as a TOML key, enum segments come from the `type` key
+fn format_key(prefix: &str, path: &serde_path_to_error::Path) -> String {
+    let mut key = prefix.to_string();
+    for segment in path {
+        match segment {
+            Segment::Seq { index } => key.push_str(&format!("[{index}]")),
+            Segment::Map { key: name } => {
+                if !key.is_empty() {
+                    key.push('.');
+                }
+                key.push_str(name);
+            }
+            Segment::Enum { .. } | Segment::Unknown => {}
+        }
+    }
+    key
+}
+
+struct SceneBuilder<'a> {
+    file: &'a Path,
+    directory: &'a Path,
+    textures: HashMap<String, Textures>,
+    materials: HashMap<String, Materials>,
+    prototypes: HashMap<String, Arc<Hittables>>,
+}
+
+impl SceneBuilder<'_> {
+    // Deserializes a table whose `type` key selects the enum variant
+    fn parse_tagged<T: DeserializeOwned>(
+        &self,
+        mut table: Table,
+        key: &str,
+    ) -> Result<T, SceneError> {
+        let kind = match table.remove("type") {
+            Some(Value::String(kind)) => kind,
+            Some(_) => return Err(self.invalid(&format!("{key}.type"), "must be a string")),
+            None => return Err(self.invalid(key, "missing field `type`")),
+        };
+        let value = Value::Table(Table::from_iter([(kind, Value::Table(table))]));
+
+        serde_path_to_error::deserialize(value).map_err(|error| {
+            let mut path = format_key(key, error.path());
+            // The first line is the message, the rest repeats the key
+            let message = error.inner().to_string();
+            let message = message.lines().next().unwrap_or_default();
+            // Only the outermost variant comes from the `type` key
+            if path == key && message.starts_with("unknown variant") {
+                path.push_str(".type");
+            }
+            SceneError::Parse {
+                file: self.file.to_path_buf(),
+                key: path,
+                message: message.to_string(),
+            }
+        })
+    }
+
+    fn invalid(&self, key: &str, message: &str) -> SceneError {
+        SceneError::Invalid {
+            file: self.file.to_path_buf(),
+            key: key.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    // Textures can be made of other textures, so each one is built once
+    // everything it refers to exists
+    fn build_textures(
+        &mut self,
+        mut pending: Vec<(String, TextureDescription)>,
+    ) -> Result<(), SceneError> {
+        pending.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        while !pending.is_empty() {
+            let ready = pending.iter().position(|(_, texture)| {
+                texture
+                    .references()
+                    .iter()
+                    .all(|(_, name)| self.textures.contains_key(*name))
+            });
+
+            let index = match ready {
+                Some(index) => index,
+                None => {
+                    // Every texture left waits on a missing one or on another pending one
+                    let (name, texture) = &pending[0];
+                    let (field, missing) = texture
+                        .references()
+                        .into_iter()
+                        .find(|(_, name)| !self.textures.contains_key(*name))
+                        .unwrap();
+                    let key = format!("textures.{name}.{field}");
+                    return Err(if pending.iter().any(|(name, _)| name == missing) {
+                        self.invalid(
+                            &key,
+                            &format!("texture '{missing}' refers back to '{name}'"),
+                        )
+                    } else {
+                        self.undefined_texture(&key, missing)
+                    });
+                }
+            };
+
+            let (name, texture) = pending.remove(index);
+            let texture = self.build_texture(texture, &format!("textures.{name}"))?;
+            self.textures.insert(name, texture);
+        }
+
+        Ok(())
+    }
+
+    fn build_texture(
+        &self,
+        description: TextureDescription,
+        key: &str,
+    ) -> Result<Textures, SceneError> {
+        let texture = match description {
+            TextureDescription::Solid { color } => {
+                self.color(color, &format!("{key}.color"))?.into()
+            }
+            TextureDescription::Checker { scale, even, odd } => {
+                let scale = self.positive(scale, &format!("{key}.scale"))?;
+                let even = self.texture(&even, &format!("{key}.even"))?;
+                let odd = self.texture(&odd, &format!("{key}.odd"))?;
+                CheckerTexture::new(scale, even, odd).into()
+            }
+            TextureDescription::Image { file, wrap } => {
+                let file = self.directory.join(file);
+                match ImageTexture::load(&file, wrap) {
+                    Ok(image) => image.into(),
+                    Err(error) => {
+                        return Err(SceneError::Image {
+                            key: format!("{key}.file"),
+                            file,
+                            error,
+                        })
+                    }
+                }
+            }
+            TextureDescription::Noise {
+                pattern,
+                noise,
+                seed,
+                scale,
+                octaves,
+                distortion,
+                low,
+                high,
+            } => {
+                let scale = self.positive(scale, &format!("{key}.scale"))?;
+                let mut texture = NoiseTexture::new(pattern, noise, seed, scale).colors(
+                    self.color(low, &format!("{key}.low"))?,
+                    self.color(high, &format!("{key}.high"))?,
+                );
+                if let Some(octaves) = octaves {
+                    texture = texture.octaves(octaves);
+                }
+                if let Some(distortion) = distortion {
+                    if !distortion.is_finite() {
+                        return Err(self.invalid(&format!("{key}.distortion"), "must be finite"));
+                    }
+                    texture = texture.distortion(distortion);
+                }
+                texture.into()
+            }
+        };
+        Ok(texture)
+    }
+
+    fn texture(&self, value: &ColorOrTexture, key: &str) -> Result<Textures, SceneError> {
+        match value {
+            ColorOrTexture::Color(color) => Ok(self.color(*color, key)?.into()),
+            ColorOrTexture::Texture(name) => match self.textures.get(name) {
+                Some(texture) => Ok(texture.clone()),
+                None => Err(self.undefined_texture(key, name)),
+            },
+        }
+    }
+
+    // Colors are not negative, infinite or NaN
+    fn color(&self, color: [f64; 3], key: &str) -> Result<Vec3, SceneError> {
+        if color.iter().all(|c| c.is_finite() && *c >= 0.0) {
+            Ok(Vec3::from(color))
+        } else {
+            Err(self.invalid(key, "must be finite and not negative"))
+        }
+    }
+
+    // Points and directions
+    fn vector(&self, vector: [f64; 3], key: &str) -> Result<Vec3, SceneError> {
+        if vector.iter().all(|c| c.is_finite()) {
+            Ok(Vec3::from(vector))
+        } else {
+            Err(self.invalid(key, "must be finite"))
+        }
+    }
+
+    // Unit length normals
+    fn normal(&self, normal: [f64; 3], key: &str) -> Result<Vec3, SceneError> {
+        let normal = self.vector(normal, key)?;
+        if normal.length_squared() == 0.0 {
+            return Err(self.invalid(key, "must not be zero"));
+        }
+        Ok(normal.unit_vector())
+    }
+
+    // Sizes such as radii
+    fn positive(&self, value: f64, key: &str) -> Result<f64, SceneError> {
+        if value.is_finite() && value > 0.0 {
+            Ok(value)
+        } else {
+            Err(self.invalid(key, "must be positive and finite"))
+        }
+    }
+
+    fn undefined_texture(&self, key: &str, name: &str) -> SceneError {
+        self.invalid(
+            key,
+            &format!("texture '{name}' is not defined in [textures]"),
+        )
+    }
+
+    fn build_material(
+        &self,
+        description: MaterialDescription,
+        key: &str,
+    ) -> Result<Materials, SceneError> {
+        let material = match description {
+            MaterialDescription::Lambertian { albedo } => Materials::Lambertian(
+                Lambertian::textured(self.texture(&albedo, &format!("{key}.albedo"))?),
+            ),
+            MaterialDescription::Metal { albedo, fuzziness } => {
+                if !(fuzziness.is_finite() && fuzziness >= 0.0) {
+                    return Err(self.invalid(
+                        &format!("{key}.fuzziness"),
+                        "must be finite and not negative",
+                    ));
+                }
+                Materials::Metal(Metal::textured(
+                    self.texture(&albedo, &format!("{key}.albedo"))?,
+                    fuzziness,
+                ))
+            }
+            MaterialDescription::Dielectric {
+                index_of_refraction,
+            } => Materials::Dielectric(Dielectric::new(
+                self.positive(index_of_refraction, &format!("{key}.index_of_refraction"))?,
+            )),
+            MaterialDescription::DiffuseLight { emit } => Materials::DiffuseLight(
+                DiffuseLight::new(self.color(emit, &format!("{key}.emit"))?),
+            ),
+        };
+        Ok(material)
+    }
+
+    fn material(&self, name: &str, key: &str) -> Result<Materials, SceneError> {
+        match self.materials.get(name) {
+            Some(material) => Ok(material.clone()),
+            None => Err(self.invalid(
+                &format!("{key}.material"),
+                &format!("material '{name}' is not defined in [materials]"),
+            )),
+        }
+    }
+
+    fn build_object(&self, table: Table, key: &str) -> Result<Hittables, SceneError> {
+        let object = match self.parse_tagged(table, key)? {
+            ObjectDescription::Sphere {
+                center,
+                radius,
+                material,
+            } => Hittables::Sphere(Sphere::new(
+                self.vector(center, &format!("{key}.center"))?,
+                self.positive(radius, &format!("{key}.radius"))?,
+                self.material(&material, key)?,
+            )),
+            ObjectDescription::MovingSphere {
+                center0,
+                center1,
+                radius,
+                material,
+            } => Hittables::MovingSphere(MovingSphere::new(
+                self.vector(center0, &format!("{key}.center0"))?,
+                self.vector(center1, &format!("{key}.center1"))?,
+                self.positive(radius, &format!("{key}.radius"))?,
+                self.material(&material, key)?,
+            )),
+            ObjectDescription::Triangle {
+                vertices,
+                normals,
+                uvs,
+                material,
+            } => Hittables::Triangle(Triangle::with_vertex_data(
+                [
+                    self.vector(vertices[0], &format!("{key}.vertices[0]"))?,
+                    self.vector(vertices[1], &format!("{key}.vertices[1]"))?,
+                    self.vector(vertices[2], &format!("{key}.vertices[2]"))?,
+                ],
+                match normals {
+                    Some(normals) => Some([
+                        self.normal(normals[0], &format!("{key}.normals[0]"))?,
+                        self.normal(normals[1], &format!("{key}.normals[1]"))?,
+                        self.normal(normals[2], &format!("{key}.normals[2]"))?,
+                    ]),
+                    None => None,
+                },
+                match uvs {
+                    Some(uvs) if uvs.iter().flatten().all(|c| c.is_finite()) => {
+                        uvs.map(|uv| (uv[0], uv[1]))
+                    }
+                    Some(_) => return Err(self.invalid(&format!("{key}.uvs"), "must be finite")),
+                    None => Triangle::DEFAULT_UVS,
+                },
+                self.material(&material, key)?,
+            )),
+            ObjectDescription::Quad { q, u, v, material } => {
+                let u_key = format!("{key}.u");
+                let u = self.vector(u, &u_key)?;
+                let v = self.vector(v, &format!("{key}.v"))?;
+                // The quad would have no area and no normal
+                let area_squared = Vec3::cross(u, v).length_squared();
+                if area_squared == 0.0 || !area_squared.is_finite() {
+                    return Err(self.invalid(&u_key, "must not be zero or parallel to v"));
+                }
+                Hittables::Quad(Quad::new(
+                    self.vector(q, &format!("{key}.q"))?,
+                    u,
+                    v,
+                    self.material(&material, key)?,
+                ))
+            }
+            ObjectDescription::Box { min, max, material } => {
+                let min = self.vector(min, &format!("{key}.min"))?;
+                let max_key = format!("{key}.max");
+                let max = self.vector(max, &max_key)?;
+                // Some of the sides would have no area and no normal
+                if min.x == max.x || min.y == max.y || min.z == max.z {
+                    return Err(self.invalid(&max_key, "must differ from min along every axis"));
+                }
+                Hittables::Cuboid(Cuboid::new(min, max, self.material(&material, key)?))
+            }
+            ObjectDescription::Mesh { file, material } => {
+                let material = match material {
+                    Some(name) => self.material(&name, key)?,
+                    None => MtlMaterial::DEFAULT.to_material(),
+                };
+                let mesh = load_obj(&self.directory.join(file), material).map_err(|error| {
+                    SceneError::Obj {
+                        key: format!("{key}.file"),
+                        error,
+                    }
+                })?;
+                Hittables::Mesh(mesh)
+            }
+            ObjectDescription::List { objects } => {
+                let mut list = HittableList::EMPTY;
+                for (i, object) in objects.into_iter().enumerate() {
+                    list.add(self.build_object(object, &format!("{key}.objects[{i}]"))?);
+                }
+                Hittables::List(list)
+            }
+            ObjectDescription::ConstantMedium {
+                boundary,
+                density,
+                albedo,
+                phase,
+            } => {
+                let density = self.positive(density, &format!("{key}.density"))?;
+                let phase_function = self.phase_function(phase, &format!("{key}.phase"))?;
+                let boundary = self.build_object(boundary, &format!("{key}.boundary"))?;
+                let albedo = self.texture(&albedo, &format!("{key}.albedo"))?;
+                Hittables::ConstantMedium(ConstantMedium::new(
+                    boundary,
+                    density,
+                    albedo,
+                    phase_function,
+                ))
+            }
+            ObjectDescription::HeterogeneousMedium {
+                boundary,
+                density,
+                absorption,
+                scattering,
+                emission,
+                phase,
+            } => {
+                let valid = |coefficient: f64| coefficient.is_finite() && coefficient >= 0.0;
+                if !valid(absorption) || !valid(scattering) {
+                    return Err(self.invalid(key, "coefficients must be finite and not negative"));
+                }
+                let phase_function = self.phase_function(phase, &format!("{key}.phase"))?;
+                let boundary = self.build_object(boundary, &format!("{key}.boundary"))?;
+                let density_key = format!("{key}.density");
+                let density = match self.parse_tagged(density, &density_key)? {
+                    DensityDescription::Grid { file, resolution } => {
+                        let grid = VoxelGrid::load(&self.directory.join(file), resolution)
+                            .map_err(|error| SceneError::Voxel {
+                                key: format!("{density_key}.file"),
+                                error,
+                            })?;
+                        DensityField::Grid(grid)
+                    }
+                    DensityDescription::Noise {
+                        noise,
+                        seed,
+                        scale,
+                        octaves,
+                    } => DensityField::Noise {
+                        noise: Noise::new(noise, seed),
+                        scale: self.positive(scale, &format!("{density_key}.scale"))?,
+                        octaves,
+                    },
+                };
+                Hittables::HeterogeneousMedium(HeterogeneousMedium::new(
+                    boundary,
+                    density,
+                    absorption,
+                    scattering,
+                    Vec3::from(emission),
+                    phase_function,
+                ))
+            }
+            ObjectDescription::Instance {
+                object,
+                transforms,
+                motion,
+            } => {
+                let prototype = match self.prototypes.get(&object) {
+                    Some(prototype) => Arc::clone(prototype),
+                    None => {
+                        return Err(self.invalid(
+                            &format!("{key}.object"),
+                            &format!("prototype '{object}' is not defined in [prototypes]"),
+                        ))
+                    }
+                };
+                let mut transform = Transform::IDENTITY;
+                for (i, description) in transforms.iter().enumerate() {
+                    let step = self.transform(description, &format!("{key}.transforms[{i}]"))?;
+                    transform = transform.then(step);
+                }
+                if motion.is_empty() {
+                    Hittables::Instance(Instance::new(prototype, transform))
+                } else {
+                    let steps = motion
+                        .iter()
+                        .enumerate()
+                        .map(|(i, step)| self.motion_step(step, &format!("{key}.motion[{i}]")))
+                        .collect::<Result<Vec<MotionStep>, SceneError>>()?;
+                    Hittables::Instance(Instance::animated(
+                        prototype,
+                        transform,
+                        Motion::new(steps),
+                    ))
+                }
+            }
+        };
+        Ok(object)
+    }
+
+    fn motion_step(
+        &self,
+        description: &TransformDescription,
+        key: &str,
+    ) -> Result<MotionStep, SceneError> {
+        let step = match description {
+            TransformDescription::Translate(offset) => {
+                MotionStep::Translate(self.vector(*offset, key)?)
+            }
+            TransformDescription::Scale(factors) => {
+                if !factors
+                    .iter()
+                    .all(|factor| factor.is_finite() && *factor > 0.0)
+                {
+                    return Err(self.invalid(key, "animated scale factors must be positive"));
+                }
+                MotionStep::Scale(Vec3::from(*factors))
+            }
+            TransformDescription::Rotate { axis, angle } => MotionStep::Rotate {
+                axis: self.rotation_axis(*axis, *angle, key)?,
+                degrees: *angle,
+            },
+            TransformDescription::Matrix(_) => {
+                return Err(self.invalid(key, "matrices cannot be animated"))
+            }
+        };
+        Ok(step)
+    }
+
+    fn transform(
+        &self,
+        description: &TransformDescription,
+        key: &str,
+    ) -> Result<Transform, SceneError> {
+        let transform = match description {
+            TransformDescription::Translate(offset) => {
+                Transform::IDENTITY.translate(self.vector(*offset, key)?)
+            }
+            TransformDescription::Scale(factors) => {
+                if !factors
+                    .iter()
+                    .all(|factor| factor.is_finite() && *factor != 0.0)
+                {
+                    return Err(self.invalid(key, "scale factors must be finite and not zero"));
+                }
+                Transform::IDENTITY.scale(Vec3::from(*factors))
+            }
+            TransformDescription::Rotate { axis, angle } => {
+                Transform::IDENTITY.rotate(self.rotation_axis(*axis, *angle, key)?, *angle)
+            }
+            TransformDescription::Matrix(m) => {
+                if !m.iter().flatten().all(|value| value.is_finite()) {
+                    return Err(self.invalid(key, "matrix must be finite"));
+                }
+                Transform::from_matrix(Matrix4 { m: *m })
+                    .ok_or_else(|| self.invalid(key, "matrix is not invertible"))?
+            }
+        };
+        Ok(transform)
+    }
+
+    fn rotation_axis(&self, axis: [f64; 3], angle: f64, key: &str) -> Result<Vec3, SceneError> {
+        let axis = self.vector(axis, key)?;
+        if axis.near_zero() {
+            return Err(self.invalid(key, "rotation axis must not be zero"));
+        }
+        if !angle.is_finite() {
+            return Err(self.invalid(key, "rotation angle must be finite"));
+        }
+        Ok(axis)
+    }
+
+    fn phase_function(&self, phase: Option<Table>, key: &str) -> Result<PhaseFunction, SceneError> {
+        let Some(table) = phase else {
+            return Ok(PhaseFunction::Isotropic);
+        };
+        let phase_function = match self.parse_tagged(table, key)? {
+            PhaseDescription::Isotropic => PhaseFunction::Isotropic,
+            PhaseDescription::HenyeyGreenstein { g } => {
+                if !(g > -1.0 && g < 1.0) {
+                    return Err(self.invalid(&format!("{key}.g"), "must be between -1 and 1"));
+                }
+                PhaseFunction::HenyeyGreenstein { g }
+            }
+        };
+        Ok(phase_function)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAMERA: &str = r#"
+[camera]
+look_from = [0.0, 0.0, 5.0]
+look_at = [0.0, 0.0, 0.0]
+vertical_fov = 40.0
+
+[materials.white]
+type = "lambertian"
+albedo = [0.7, 0.7, 0.7]
+"#;
+
+    // Key the error for the scene with the default camera and these objects points to
+    fn error_key(objects: &str) -> String {
+        scene_error_key(&format!("{CAMERA}\n{objects}"))
+    }
+
+    // Key the error for the default camera with one field set to value points to
+    fn camera_error_key(field: &str, value: &str) -> String {
+        let mut fields = vec![
+            ("look_from", "[0.0, 0.0, 5.0]"),
+            ("look_at", "[0.0, 0.0, 0.0]"),
+            ("vertical_fov", "40.0"),
+        ];
+        fields.retain(|(name, _)| *name != field);
+        fields.push((field, value));
+        let camera: String = fields
+            .iter()
+            .map(|(name, value)| format!("{name} = {value}\n"))
+            .collect();
+        scene_error_key(&format!("[camera]\n{camera}"))
+    }
+
+    fn scene_error_key(text: &str) -> String {
+        match parse_scene(text, Path::new("test.toml"), |_| {}) {
+            Err(SceneError::Parse { key, .. } | SceneError::Invalid { key, .. }) => key,
+            Err(error) => panic!("unexpected error {error}"),
+            Ok(_) => panic!("expected an error"),
+        }
+    }
+
+    #[test]
+    fn valid_scene_loads() {
+        let objects = r#"
+[[objects]]
+type = "sphere"
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material = "white"
+"#;
+        let text = format!("{CAMERA}\n{objects}");
+        assert!(parse_scene(&text, Path::new("test.toml"), |_| {}).is_ok());
+    }
+
+    #[test]
+    fn invalid_camera_fields_point_to_their_key() {
+        let cases = [
+            ("look_from", "[0.0, 0.0, 0.0]", "camera.look_at"),
+            ("look_from", "[0.0, nan, 5.0]", "camera.look_from"),
+            ("view_up", "[0.0, 0.0, 2.0]", "camera.view_up"),
+            ("view_up", "[0.0, 0.0, 0.0]", "camera.view_up"),
+            ("vertical_fov", "0.0", "camera.vertical_fov"),
+            ("vertical_fov", "180.0", "camera.vertical_fov"),
+            ("vertical_fov", "nan", "camera.vertical_fov"),
+            ("aperture", "-0.1", "camera.aperture"),
+            ("focus_distance", "0.0", "camera.focus_distance"),
+            ("focus_distance", "-2.0", "camera.focus_distance"),
+            ("shutter", "[0.0, inf]", "camera.shutter"),
+        ];
+        for (field, value, expected) in cases {
+            assert_eq!(
+                camera_error_key(field, value),
+                expected,
+                "{field} = {value}"
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_type_points_to_the_type_key() {
+        let key = error_key(
+            r#"
+[[objects]]
+type = "torus"
+material = "white"
+"#,
+        );
+        assert_eq!(key, "objects[0].type");
+    }
+
+    #[test]
+    fn bad_nested_field_points_to_the_field() {
+        let key = error_key(
+            r#"
+[[objects]]
+type = "constant_medium"
+density = 0.1
+albedo = [1.0, 1.0, 1.0]
+boundary = { type = "sphere", center = [0.0, 0.0, 0.0], radius = "large", material = "white" }
+"#,
+        );
+        assert_eq!(key, "objects[0].boundary.radius");
+    }
+
+    #[test]
+    fn unknown_nested_type_points_to_its_type_key() {
+        let key = error_key(
+            r#"
+[[objects]]
+type = "constant_medium"
+density = 0.1
+albedo = [1.0, 1.0, 1.0]
+boundary = { type = "cone", material = "white" }
+"#,
+        );
+        assert_eq!(key, "objects[0].boundary.type");
+    }
+
+    #[test]
+    fn sphere_radius_must_be_positive() {
+        for radius in ["0.0", "-1.0", "nan", "inf"] {
+            let key = error_key(&format!(
+                r#"
+[[objects]]
+type = "sphere"
+center = [0.0, 0.0, 0.0]
+radius = {radius}
+material = "white"
+"#
+            ));
+            assert_eq!(key, "objects[0].radius");
+        }
+    }
+
+    #[test]
+    fn non_finite_position_points_to_its_key() {
+        let key = error_key(
+            r#"
+[[objects]]
+type = "moving_sphere"
+center0 = [0.0, 0.0, 0.0]
+center1 = [0.0, nan, 0.0]
+radius = 1.0
+material = "white"
+"#,
+        );
+        assert_eq!(key, "objects[0].center1");
+    }
+
+    #[test]
+    fn index_of_refraction_must_be_positive() {
+        for index in ["0.0", "-1.5", "nan", "inf"] {
+            let key = error_key(&format!(
+                r#"
+[materials.glass]
+type = "dielectric"
+index_of_refraction = {index}
+"#
+            ));
+            assert_eq!(key, "materials.glass.index_of_refraction");
+        }
+    }
+
+    #[test]
+    fn non_finite_colors_point_to_their_key() {
+        let key = error_key(
+            r#"
+[materials.lamp]
+type = "diffuse_light"
+emit = [4.0, inf, 4.0]
+"#,
+        );
+        assert_eq!(key, "materials.lamp.emit");
+
+        let key = error_key(
+            r#"
+[materials.steel]
+type = "metal"
+albedo = [0.8, nan, 0.8]
+"#,
+        );
+        assert_eq!(key, "materials.steel.albedo");
+
+        let key = error_key(
+            r#"
+[materials.steel]
+type = "metal"
+albedo = [0.8, 0.8, 0.8]
+fuzziness = nan
+"#,
+        );
+        assert_eq!(key, "materials.steel.fuzziness");
+    }
+
+    #[test]
+    fn degenerate_quad_points_to_the_u_key() {
+        for u in ["[2.0, 0.0, 0.0]", "[0.0, 0.0, 0.0]"] {
+            let key = error_key(&format!(
+                r#"
+[[objects]]
+type = "quad"
+q = [0.0, 0.0, 0.0]
+u = {u}
+v = [1.0, 0.0, 0.0]
+material = "white"
+"#
+            ));
+            assert_eq!(key, "objects[0].u");
+        }
+    }
+
+    #[test]
+    fn zero_normal_points_to_its_key() {
+        let key = error_key(
+            r#"
+[[objects]]
+type = "triangle"
+vertices = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+normals = [[0.0, 0.0, 1.0], [0.0, 0.0, 0.0], [0.0, 0.0, 1.0]]
+material = "white"
+"#,
+        );
+        assert_eq!(key, "objects[0].normals[1]");
+    }
+
+    #[test]
+    fn non_finite_sizes_point_to_their_key() {
+        let key = error_key(
+            r#"
+[[objects]]
+type = "constant_medium"
+density = inf
+albedo = [1.0, 1.0, 1.0]
+boundary = { type = "sphere", center = [0.0, 0.0, 0.0], radius = 1.0, material = "white" }
+"#,
+        );
+        assert_eq!(key, "objects[0].density");
+
+        let key = error_key(
+            r#"
+[textures.marble]
+type = "noise"
+pattern = "marble"
+scale = nan
+"#,
+        );
+        assert_eq!(key, "textures.marble.scale");
+    }
+
+    #[test]
+    fn invalid_transforms_point_to_the_transform() {
+        let prototype = r#"
+[prototypes.ball]
+type = "sphere"
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material = "white"
+"#;
+        for (list, transform) in [
+            ("transforms", "{ translate = [0.0, nan, 0.0] }"),
+            ("transforms", "{ scale = [1.0, nan, 1.0] }"),
+            ("transforms", "{ scale = [1.0, 0.0, 1.0] }"),
+            ("motion", "{ scale = [1.0, nan, 1.0] }"),
+            ("motion", "{ translate = [inf, 0.0, 0.0] }"),
+            (
+                "motion",
+                "{ rotate = { axis = [0.0, 1.0, 0.0], angle = nan } }",
+            ),
+        ] {
+            let key = error_key(&format!(
+                r#"{prototype}
+[[objects]]
+type = "instance"
+object = "ball"
+{list} = [{transform}]
+"#
+            ));
+            assert_eq!(key, format!("objects[0].{list}[0]"), "{transform}");
+        }
+    }
+
+    #[test]
+    fn flat_box_points_to_the_max_key() {
+        let key = error_key(
+            r#"
+[[objects]]
+type = "box"
+min = [0.0, 0.0, 0.0]
+max = [1.0, 0.0, 1.0]
+material = "white"
+"#,
+        );
+        assert_eq!(key, "objects[0].max");
+    }
+
+    #[test]
+    fn missing_material_points_to_the_material_key() {
+        let key = error_key(
+            r#"
+[[objects]]
+type = "sphere"
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material = "glass"
+"#,
+        );
+        assert_eq!(key, "objects[0].material");
+    }
+}
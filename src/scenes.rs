@@ -0,0 +1,652 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::background::Background;
+use crate::camera::Camera;
+use crate::hittable_list::HittableList;
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Materials, Metal};
+use crate::medium::ConstantMedium;
+use crate::noise::NoiseKind;
+use crate::obj::{load_obj, ObjError};
+use crate::phase::PhaseFunction;
+use crate::quad::{Cuboid, Quad};
+use crate::settings::RenderSettings;
+use crate::sphere::{MovingSphere, Sphere};
+use crate::texture::{CheckerTexture, ImageTexture, NoisePattern, NoiseTexture, WrapMode};
+use crate::transform::{Instance, Motion, MotionStep, Transform};
+use crate::triangle::Triangle;
+use crate::utilities::{random_double, random_double_from_range};
+use crate::{Color, Point3, Vec3};
+
+pub struct Scene {
+    pub world: HittableList,
+    pub camera: Camera,
+    pub background: Background,
+}
+
+pub const BUILTIN_SCENES: [&str; 10] = [
+    "final",
+    "triangles",
+    "meshes",
+    "simple_light",
+    "cornell_box",
+    "instances",
+    "textures",
+    "noise",
+    "motion_blur",
+    "smoke_boxes",
+];
+
+// Render settings a built-in scene is composed for, before command line overrides
+pub fn builtin_settings(name: &str) -> RenderSettings {
+    let mut settings = RenderSettings::default();
+    if name == "cornell_box" || name == "smoke_boxes" {
+        settings.aspect_ratio = 1.0;
+        settings.image_width = 300;
+        settings.samples_per_pixel = 200;
+    }
+    settings
+}
+
+// None when there is no built-in scene with this name
+pub fn builtin_scene(name: &str, aspect_ratio: f64) -> Option<Result<Scene, ObjError>> {
+    match name {
+        "final" => Some(Ok(final_scene(aspect_ratio))),
+        "triangles" => Some(Ok(triangles(aspect_ratio))),
+        "meshes" => Some(meshes(aspect_ratio)),
+        "simple_light" => Some(Ok(simple_light(aspect_ratio))),
+        "cornell_box" => Some(Ok(cornell_box(aspect_ratio))),
+        "instances" => Some(instances(aspect_ratio)),
+        "textures" => Some(textures(aspect_ratio)),
+        "noise" => Some(Ok(noise(aspect_ratio))),
+        "motion_blur" => Some(Ok(motion_blur(aspect_ratio))),
+        "smoke_boxes" => Some(Ok(smoke_boxes(aspect_ratio))),
+        _ => None,
+    }
+}
+
+pub fn final_scene(aspect_ratio: f64) -> Scene {
+    // World
+    let mut world = HittableList::EMPTY;
+
+    let material_ground = Materials::Lambertian(Lambertian::new(&Color::new(0.5, 0.5, 0.5)));
+    world.add(Sphere::new(
+        Point3::new(0.0, -1000.0, -1.0),
+        1000.0,
+        material_ground,
+    ));
+
+    let center_comparison_pt = Point3::new(4.0, 0.2, 0.0);
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_material = random_double();
+
+            let center = Point3::new(
+                (a as f64) + 0.9 * random_double(),
+                0.2,
+                (b as f64) + 0.9 * random_double(),
+            );
+
+            if (center - center_comparison_pt).length() > 0.9 {
+                let sphere_material: Materials;
+
+                if choose_material < 0.8 {
+                    // diffuse
+                    let albedo = Color::random() * Color::random();
+                    sphere_material = Materials::Lambertian(Lambertian::new(&albedo));
+                } else if choose_material < 0.95 {
+                    // metal
+                    let albedo = Color::random_from_range(0.5, 1.0);
+                    let fuzziness = random_double_from_range(0.0, 0.5);
+                    sphere_material = Materials::Metal(Metal::new(albedo, fuzziness));
+                } else {
+                    sphere_material = Materials::Dielectric(Dielectric::new(1.5));
+                }
+                world.add(Sphere::new(center, 0.2, sphere_material));
+            }
+        }
+    }
+
+    let material_1 = Materials::Dielectric(Dielectric::new(1.5));
+    world.add(Sphere::new(Point3::y(1.0), 1.0, material_1));
+
+    let material_2 = Materials::Lambertian(Lambertian::new(&Color::new(0.4, 0.4, 0.1)));
+    world.add(Sphere::new(Point3::new(-4.0, 1.0, 0.0), 1.0, material_2));
+
+    let material_3 = Materials::Metal(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
+    world.add(Sphere::new(Point3::new(4.0, 1.0, 0.0), 1.0, material_3));
+
+    let camera = Camera::new(
+        Point3::new(13.0, 2.0, 3.0),
+        Point3::ZERO,
+        Vec3::y(1.0),
+        20.0,
+        aspect_ratio,
+        0.1,
+        10.0,
+    );
+
+    Scene {
+        world,
+        camera,
+        background: Background::SKY,
+    }
+}
+
+pub fn triangles(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::EMPTY;
+
+    let material_ground = Materials::Lambertian(Lambertian::new(&Color::new(0.5, 0.5, 0.5)));
+    world.add(Sphere::new(
+        Point3::new(0.0, -1000.0, -1.0),
+        1000.0,
+        material_ground,
+    ));
+
+    // Octahedron with per-vertex normals pointing away from its center, shades like a sphere
+    let center = Point3::new(-2.5, 1.0, 0.0);
+    let corners = [Vec3::x(1.0), Vec3::z(1.0), Vec3::x(-1.0), Vec3::z(-1.0)];
+    for pole in [Vec3::y(1.0), Vec3::y(-1.0)] {
+        for i in 0..4 {
+            let directions = [pole, corners[i], corners[(i + 1) % 4]];
+            let material = Materials::Lambertian(Lambertian::new(&Color::new(0.2, 0.4, 0.8)));
+            world.add(Triangle::with_vertex_data(
+                directions.map(|d| center + d),
+                Some(directions),
+                Triangle::DEFAULT_UVS,
+                material,
+            ));
+        }
+    }
+
+    // Flat shaded tetrahedron
+    let tetrahedron = [
+        Point3::new(0.5, 0.0, -1.0),
+        Point3::new(2.0, 0.0, 1.0),
+        Point3::new(3.0, 0.0, -1.5),
+        Point3::new(1.8, 2.0, -0.5),
+    ];
+    for face in [[0, 1, 3], [1, 2, 3], [2, 0, 3]] {
+        let material = Materials::Metal(Metal::new(Color::new(0.8, 0.6, 0.2), 0.05));
+        world.add(Triangle::new(face.map(|i| tetrahedron[i]), material));
+    }
+
+    let material_glass = Materials::Dielectric(Dielectric::new(1.5));
+    world.add(Triangle::new(
+        [
+            Point3::new(-0.5, 0.2, 2.0),
+            Point3::new(1.5, 0.2, 2.5),
+            Point3::new(0.5, 1.8, 2.2),
+        ],
+        material_glass,
+    ));
+
+    let camera = Camera::new(
+        Point3::new(13.0, 2.0, 3.0),
+        Point3::new(0.0, 0.8, 0.0),
+        Vec3::y(1.0),
+        20.0,
+        aspect_ratio,
+        0.0,
+        10.0,
+    );
+
+    Scene {
+        world,
+        camera,
+        background: Background::SKY,
+    }
+}
+
+pub fn meshes(aspect_ratio: f64) -> Result<Scene, ObjError> {
+    let mut world = HittableList::EMPTY;
+
+    let material_ground = Materials::Lambertian(Lambertian::new(&Color::new(0.5, 0.5, 0.5)));
+    world.add(Sphere::new(
+        Point3::new(0.0, -1000.5, 0.0),
+        1000.0,
+        material_ground,
+    ));
+
+    let material_torus = Materials::Metal(Metal::new(Color::new(0.8, 0.8, 0.9), 0.1));
+    world.add(load_obj(Path::new("models/torus.obj"), material_torus)?);
+
+    let material_cube = Materials::Lambertian(Lambertian::new(&Color::new(0.7, 0.2, 0.2)));
+    world.add(load_obj(Path::new("models/cube.obj"), material_cube)?);
+
+    let camera = Camera::new(
+        Point3::new(4.0, 3.0, 4.0),
+        Point3::ZERO,
+        Vec3::y(1.0),
+        35.0,
+        aspect_ratio,
+        0.0,
+        10.0,
+    );
+
+    Ok(Scene {
+        world,
+        camera,
+        background: Background::SKY,
+    })
+}
+
+// Lit only by emissive objects
+pub fn simple_light(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::EMPTY;
+
+    let material_ground = Materials::Lambertian(Lambertian::new(&Color::new(0.5, 0.5, 0.5)));
+    world.add(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        material_ground,
+    ));
+
+    let material_sphere = Materials::Lambertian(Lambertian::new(&Color::new(0.7, 0.3, 0.2)));
+    world.add(Sphere::new(Point3::y(2.0), 2.0, material_sphere));
+
+    let material_mirror = Materials::Metal(Metal::new(Color::new(0.8, 0.85, 0.9), 0.0));
+    world.add(Sphere::new(
+        Point3::new(0.0, 1.0, 3.5),
+        1.0,
+        material_mirror,
+    ));
+
+    // Rectangular panel made of two triangles behind the spheres
+    let corners = [
+        Point3::new(3.0, 1.0, -2.0),
+        Point3::new(5.0, 1.0, -2.0),
+        Point3::new(5.0, 3.0, -2.0),
+        Point3::new(3.0, 3.0, -2.0),
+    ];
+    for face in [[0, 1, 2], [0, 2, 3]] {
+        let material_light = Materials::DiffuseLight(DiffuseLight::new(Color::new(4.0, 4.0, 4.0)));
+        world.add(Triangle::new(face.map(|i| corners[i]), material_light));
+    }
+
+    let material_light = Materials::DiffuseLight(DiffuseLight::new(Color::new(4.0, 4.0, 4.0)));
+    world.add(Sphere::new(Point3::y(7.0), 2.0, material_light));
+
+    let camera = Camera::new(
+        Point3::new(26.0, 3.0, 6.0),
+        Point3::y(2.0),
+        Vec3::y(1.0),
+        20.0,
+        aspect_ratio,
+        0.0,
+        10.0,
+    );
+
+    Scene {
+        world,
+        camera,
+        background: Background::BLACK,
+    }
+}
+
+pub fn cornell_box(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::EMPTY;
+
+    let red = Materials::Lambertian(Lambertian::new(&Color::new(0.65, 0.05, 0.05)));
+    let white = Materials::Lambertian(Lambertian::new(&Color::new(0.73, 0.73, 0.73)));
+    let green = Materials::Lambertian(Lambertian::new(&Color::new(0.12, 0.45, 0.15)));
+    let light = Materials::DiffuseLight(DiffuseLight::new(Color::new(15.0, 15.0, 15.0)));
+
+    world.add(Quad::yz_rect((0.0, 555.0), (0.0, 555.0), 555.0, green));
+    world.add(Quad::yz_rect((0.0, 555.0), (0.0, 555.0), 0.0, red));
+    // facing down into the box
+    world.add(Quad::new(
+        Point3::new(213.0, 554.0, 227.0),
+        Vec3::x(130.0),
+        Vec3::z(105.0),
+        light,
+    ));
+    world.add(Quad::xz_rect(
+        (0.0, 555.0),
+        (0.0, 555.0),
+        0.0,
+        white.clone(),
+    ));
+    world.add(Quad::xz_rect(
+        (0.0, 555.0),
+        (0.0, 555.0),
+        555.0,
+        white.clone(),
+    ));
+    world.add(Quad::xy_rect(
+        (0.0, 555.0),
+        (0.0, 555.0),
+        555.0,
+        white.clone(),
+    ));
+
+    let tall_box = Cuboid::new(
+        Point3::ZERO,
+        Point3::new(165.0, 330.0, 165.0),
+        white.clone(),
+    );
+    world.add(Instance::new(
+        Arc::new(tall_box.into()),
+        Transform::IDENTITY
+            .rotate(Vec3::y(1.0), 15.0)
+            .translate(Vec3::new(265.0, 0.0, 295.0)),
+    ));
+
+    let short_box = Cuboid::new(Point3::ZERO, Point3::new(165.0, 165.0, 165.0), white);
+    world.add(Instance::new(
+        Arc::new(short_box.into()),
+        Transform::IDENTITY
+            .rotate(Vec3::y(1.0), -18.0)
+            .translate(Vec3::new(130.0, 0.0, 65.0)),
+    ));
+
+    let camera = Camera::new(
+        Point3::new(278.0, 278.0, -800.0),
+        Point3::new(278.0, 278.0, 0.0),
+        Vec3::y(1.0),
+        40.0,
+        aspect_ratio,
+        0.0,
+        10.0,
+    );
+
+    Scene {
+        world,
+        camera,
+        background: Background::BLACK,
+    }
+}
+
+// One torus mesh placed many times with different transforms
+pub fn instances(aspect_ratio: f64) -> Result<Scene, ObjError> {
+    let mut world = HittableList::EMPTY;
+
+    let material_ground = Materials::Lambertian(Lambertian::new(&Color::new(0.5, 0.5, 0.5)));
+    world.add(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        material_ground,
+    ));
+
+    let material_torus = Materials::Metal(Metal::new(Color::new(0.8, 0.6, 0.3), 0.2));
+    let torus = Arc::new(load_obj(Path::new("models/torus.obj"), material_torus)?.into());
+
+    for a in -3..=3 {
+        for b in -3..=3 {
+            let scale = Vec3::new(
+                random_double_from_range(0.3, 0.6),
+                random_double_from_range(0.3, 0.6),
+                random_double_from_range(0.3, 0.6),
+            );
+            let axis = Vec3::random_from_range(-1.0, 1.0);
+            let transform = Transform::IDENTITY
+                .scale(scale)
+                .rotate(axis, random_double_from_range(0.0, 360.0))
+                .translate(Vec3::new(1.5 * a as f64, 0.6, 1.5 * b as f64));
+            world.add(Instance::new(Arc::clone(&torus), transform));
+        }
+    }
+
+    let camera = Camera::new(
+        Point3::new(9.0, 6.0, 9.0),
+        Point3::ZERO,
+        Vec3::y(1.0),
+        35.0,
+        aspect_ratio,
+        0.0,
+        10.0,
+    );
+
+    Ok(Scene {
+        world,
+        camera,
+        background: Background::SKY,
+    })
+}
+
+// Checkered ground, an image wrapped around a sphere and the textured cube mesh
+pub fn textures(aspect_ratio: f64) -> Result<Scene, ObjError> {
+    let mut world = HittableList::EMPTY;
+
+    let checker = CheckerTexture::new(0.5, Color::new(0.2, 0.3, 0.1), Color::new(0.9, 0.9, 0.9));
+    world.add(Sphere::new(
+        Point3::new(0.0, -1000.5, 0.0),
+        1000.0,
+        Materials::Lambertian(Lambertian::textured(checker)),
+    ));
+
+    let path = Path::new("models/uv_grid.png");
+    let grid = ImageTexture::load(path, WrapMode::Repeat).map_err(|error| ObjError::Texture {
+        file: path.to_path_buf(),
+        error,
+    })?;
+    world.add(Sphere::new(
+        Point3::new(-1.2, 0.3, 0.6),
+        0.8,
+        Materials::Lambertian(Lambertian::textured(grid)),
+    ));
+
+    let checker = CheckerTexture::new(0.1, Color::new(0.9, 0.7, 0.3), Color::new(0.6, 0.6, 0.6));
+    world.add(Sphere::new(
+        Point3::new(0.6, -0.1, 1.6),
+        0.4,
+        Materials::Metal(Metal::textured(checker, 0.05)),
+    ));
+
+    let material_cube = Materials::Lambertian(Lambertian::new(&Color::new(0.7, 0.2, 0.2)));
+    world.add(load_obj(Path::new("models/cube.obj"), material_cube)?);
+
+    let camera = Camera::new(
+        Point3::new(4.0, 3.0, 4.0),
+        Point3::ZERO,
+        Vec3::y(1.0),
+        35.0,
+        aspect_ratio,
+        0.0,
+        10.0,
+    );
+
+    Ok(Scene {
+        world,
+        camera,
+        background: Background::SKY,
+    })
+}
+
+// A row of spheres, one for each procedural noise pattern
+pub fn noise(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::EMPTY;
+
+    let ground = NoiseTexture::new(NoisePattern::Fbm, NoiseKind::Simplex, 1, 0.5)
+        .colors(Color::new(0.2, 0.25, 0.15), Color::new(0.5, 0.55, 0.4));
+    world.add(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Materials::Lambertian(Lambertian::textured(ground)),
+    ));
+
+    let marble = NoiseTexture::new(NoisePattern::Marble, NoiseKind::Perlin, 2, 4.0)
+        .colors(Color::new(0.3, 0.3, 0.35), Color::new(0.9, 0.9, 0.85));
+    let wood = NoiseTexture::new(NoisePattern::Wood, NoiseKind::Perlin, 3, 6.0)
+        .colors(Color::new(0.35, 0.18, 0.07), Color::new(0.65, 0.42, 0.2))
+        .distortion(4.0);
+    let turbulence = NoiseTexture::new(NoisePattern::Turbulence, NoiseKind::Simplex, 4, 3.0)
+        .colors(Color::new(0.05, 0.1, 0.3), Color::new(0.9, 0.95, 1.0));
+    let cells = NoiseTexture::new(NoisePattern::Cells, NoiseKind::Perlin, 5, 5.0)
+        .colors(Color::new(0.9, 0.6, 0.2), Color::new(0.3, 0.05, 0.05));
+    let borders = NoiseTexture::new(NoisePattern::CellBorders, NoiseKind::Perlin, 6, 5.0)
+        .colors(Color::new(0.05, 0.05, 0.05), Color::new(0.8, 0.85, 0.9));
+
+    let materials = [
+        Materials::Lambertian(Lambertian::textured(marble)),
+        Materials::Lambertian(Lambertian::textured(wood)),
+        Materials::Lambertian(Lambertian::textured(turbulence)),
+        Materials::Lambertian(Lambertian::textured(cells)),
+        Materials::Metal(Metal::textured(borders, 0.1)),
+    ];
+    for (i, material) in materials.into_iter().enumerate() {
+        let x = 2.2 * (i as f64 - 2.0);
+        world.add(Sphere::new(Point3::new(x, 1.0, 0.0), 1.0, material));
+    }
+
+    let camera = Camera::new(
+        Point3::new(0.0, 4.0, 14.0),
+        Point3::y(1.0),
+        Vec3::y(1.0),
+        40.0,
+        aspect_ratio,
+        0.0,
+        10.0,
+    );
+
+    Scene {
+        world,
+        camera,
+        background: Background::SKY,
+    }
+}
+
+// Spheres bouncing up and a spinning box, blurred over the open shutter
+pub fn motion_blur(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::EMPTY;
+
+    let checker = CheckerTexture::new(0.5, Color::new(0.2, 0.3, 0.1), Color::new(0.9, 0.9, 0.9));
+    world.add(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Materials::Lambertian(Lambertian::textured(checker)),
+    ));
+
+    for i in 0..5 {
+        let center = Point3::new(1.2 * (i as f64 - 2.0), 0.4, 1.5);
+        let bounce = Vec3::y(0.15 * (i + 1) as f64);
+        let material = Materials::Lambertian(Lambertian::new(&Color::new(
+            0.2 * i as f64,
+            0.4,
+            0.9 - 0.2 * i as f64,
+        )));
+        world.add(MovingSphere::new(center, center + bounce, 0.4, material));
+    }
+
+    let white = Materials::Lambertian(Lambertian::new(&Color::new(0.8, 0.8, 0.8)));
+    // Centered on the y axis so it spins in place
+    let cube = Cuboid::new(
+        Point3::new(-0.6, 0.0, -0.6),
+        Point3::new(0.6, 1.2, 0.6),
+        white,
+    );
+    world.add(Instance::animated(
+        Arc::new(cube.into()),
+        Transform::IDENTITY.translate(Vec3::new(-1.5, 0.0, -1.0)),
+        Motion::new(vec![MotionStep::Rotate {
+            axis: Vec3::y(1.0),
+            degrees: 30.0,
+        }]),
+    ));
+
+    let glass = Materials::Dielectric(Dielectric::new(1.5));
+    world.add(Sphere::new(Point3::new(1.5, 0.8, -1.0), 0.8, glass));
+
+    let camera = Camera::new(
+        Point3::new(0.0, 3.0, 8.0),
+        Point3::y(0.5),
+        Vec3::y(1.0),
+        35.0,
+        aspect_ratio,
+        0.0,
+        10.0,
+    );
+
+    Scene {
+        world,
+        camera,
+        background: Background::SKY,
+    }
+}
+
+// Cornell box with the two boxes made of dark and light smoke
+pub fn smoke_boxes(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::EMPTY;
+
+    let red = Materials::Lambertian(Lambertian::new(&Color::new(0.65, 0.05, 0.05)));
+    let white = Materials::Lambertian(Lambertian::new(&Color::new(0.73, 0.73, 0.73)));
+    let green = Materials::Lambertian(Lambertian::new(&Color::new(0.12, 0.45, 0.15)));
+    let light = Materials::DiffuseLight(DiffuseLight::new(Color::new(7.0, 7.0, 7.0)));
+
+    world.add(Quad::yz_rect((0.0, 555.0), (0.0, 555.0), 555.0, green));
+    world.add(Quad::yz_rect((0.0, 555.0), (0.0, 555.0), 0.0, red));
+    // facing down into the box
+    world.add(Quad::new(
+        Point3::new(113.0, 554.0, 127.0),
+        Vec3::x(330.0),
+        Vec3::z(305.0),
+        light,
+    ));
+    world.add(Quad::xz_rect(
+        (0.0, 555.0),
+        (0.0, 555.0),
+        0.0,
+        white.clone(),
+    ));
+    world.add(Quad::xz_rect(
+        (0.0, 555.0),
+        (0.0, 555.0),
+        555.0,
+        white.clone(),
+    ));
+    world.add(Quad::xy_rect(
+        (0.0, 555.0),
+        (0.0, 555.0),
+        555.0,
+        white.clone(),
+    ));
+
+    let tall_box = Cuboid::new(
+        Point3::ZERO,
+        Point3::new(165.0, 330.0, 165.0),
+        white.clone(),
+    );
+    let tall_box = Instance::new(
+        Arc::new(tall_box.into()),
+        Transform::IDENTITY
+            .rotate(Vec3::y(1.0), 15.0)
+            .translate(Vec3::new(265.0, 0.0, 295.0)),
+    );
+    world.add(ConstantMedium::new(
+        tall_box,
+        0.01,
+        Color::ZERO,
+        PhaseFunction::Isotropic,
+    ));
+
+    let short_box = Cuboid::new(Point3::ZERO, Point3::new(165.0, 165.0, 165.0), white);
+    let short_box = Instance::new(
+        Arc::new(short_box.into()),
+        Transform::IDENTITY
+            .rotate(Vec3::y(1.0), -18.0)
+            .translate(Vec3::new(130.0, 0.0, 65.0)),
+    );
+    world.add(ConstantMedium::new(
+        short_box,
+        0.01,
+        Color::ONE,
+        PhaseFunction::Isotropic,
+    ));
+
+    let camera = Camera::new(
+        Point3::new(278.0, 278.0, -800.0),
+        Point3::new(278.0, 278.0, 0.0),
+        Vec3::y(1.0),
+        40.0,
+        aspect_ratio,
+        0.0,
+        10.0,
+    );
+
+    Scene {
+        world,
+        camera,
+        background: Background::BLACK,
+    }
+}
@@ -0,0 +1,141 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::sampler::SamplerKind;
+use crate::tile::TileOrder;
+
+#[derive(Copy, Clone, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Png,
+    Ppm,
+    Both,
+}
+
+pub struct RenderSettings {
+    pub image_width: u32,
+    pub aspect_ratio: f64,
+    // with adaptive sampling the minimum per pixel
+    pub samples_per_pixel: u32,
+    // pixels are sampled until their estimated error is below the threshold,
+    // every pixel gets samples_per_pixel samples when there is none
+    pub adaptive_threshold: Option<f64>,
+    // four times samples_per_pixel when not given
+    pub max_samples_per_pixel: Option<u32>,
+    // Russian roulette ends paths, the cap on bounces is only a safety limit that
+    // ends every path still alive after that many bounces
+    pub max_depth: i32,
+    // bounces before Russian roulette may end a path
+    pub roulette_depth: i32,
+    // the same seed always gives the same image
+    pub seed: u64,
+    pub sampler: SamplerKind,
+    // edge length in pixels of the squares the threads render
+    pub tile_size: u32,
+    pub tile_order: TileOrder,
+    // seconds between writing the image while it is rendered, only at the end when None
+    pub save_interval: Option<f64>,
+    // path without extension, the format decides which files are written
+    pub output: String,
+    pub format: OutputFormat,
+}
+
+impl RenderSettings {
+    pub fn image_height(&self) -> u32 {
+        (self.image_width as f64 / self.aspect_ratio) as u32
+    }
+
+    // Samples a pixel may get, only adaptive sampling stops before
+    pub fn max_samples(&self) -> u32 {
+        match self.adaptive_threshold {
+            Some(_) => self
+                .max_samples_per_pixel
+                .unwrap_or(self.samples_per_pixel.saturating_mul(4)),
+            None => self.samples_per_pixel,
+        }
+    }
+
+    // Pixel coordinates are divided by width - 1 and height - 1
+    pub fn validate(&self) -> Result<(), String> {
+        if self.aspect_ratio <= 0.0 {
+            return Err(String::from("aspect ratio must be positive"));
+        }
+        if self.image_width < 2 || self.image_height() < 2 {
+            return Err(format!(
+                "image must be at least 2x2 pixels, got {}x{}",
+                self.image_width,
+                self.image_height()
+            ));
+        }
+        if self.samples_per_pixel == 0 {
+            return Err(String::from("samples per pixel must be positive"));
+        }
+        if self
+            .adaptive_threshold
+            .is_some_and(|threshold| threshold.is_nan() || threshold <= 0.0)
+        {
+            return Err(String::from("adaptive threshold must be positive"));
+        }
+        if self.max_samples_per_pixel.is_some() && self.adaptive_threshold.is_none() {
+            return Err(String::from(
+                "max samples per pixel needs an adaptive threshold",
+            ));
+        }
+        if self.max_samples() < self.samples_per_pixel {
+            return Err(format!(
+                "max samples per pixel must be at least the {} samples per pixel",
+                self.samples_per_pixel
+            ));
+        }
+        if self.tile_size == 0 {
+            return Err(String::from("tile size must be positive"));
+        }
+        if self
+            .save_interval
+            .is_some_and(|interval| interval.is_nan() || interval <= 0.0)
+        {
+            return Err(String::from("save interval must be positive"));
+        }
+        if self.max_depth < 1 {
+            return Err(String::from("max depth must be at least 1"));
+        }
+        if self.roulette_depth < 0 {
+            return Err(String::from("roulette depth must not be negative"));
+        }
+        Ok(())
+    }
+
+    // An output given with a known extension picks the format
+    pub fn set_output(&mut self, output: &str) {
+        if let Some(stem) = output.strip_suffix(".png") {
+            self.output = stem.to_string();
+            self.format = OutputFormat::Png;
+        } else if let Some(stem) = output.strip_suffix(".ppm") {
+            self.output = stem.to_string();
+            self.format = OutputFormat::Ppm;
+        } else {
+            self.output = output.to_string();
+        }
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            image_width: 100,
+            aspect_ratio: 3.0 / 2.0,
+            samples_per_pixel: 500,
+            adaptive_threshold: None,
+            max_samples_per_pixel: None,
+            max_depth: 1000,
+            roulette_depth: 3,
+            seed: 0,
+            sampler: SamplerKind::Independent,
+            tile_size: 16,
+            tile_order: TileOrder::Spiral,
+            save_interval: None,
+            output: String::from("images/final_image_111"),
+            format: OutputFormat::Both,
+        }
+    }
+}
@@ -1,7 +1,11 @@
+use std::f64::consts::PI;
+
+use crate::aabb::Aabb;
 use crate::hittable::{HitRecord, Hittable};
 use crate::material::Materials;
-use crate::vec3::dot;
-use crate::{Point3, Ray};
+use crate::sampling::{uniform_cone, uniform_cone_pdf, uniform_sphere};
+use crate::vec3::{dot, Onb};
+use crate::{Point3, Ray, Vec3};
 
 pub struct Sphere {
     pub center: Point3,
@@ -17,41 +21,213 @@ impl Sphere {
             material,
         }
     }
+
+    // Unit direction towards the visible part of the sphere and its pdf with respect to
+    // solid angle. From outside the cone of directions the sphere covers is sampled
+    // uniformly, from inside the whole surface is.
+    pub fn sample_direction(&self, origin: Point3, u: [f64; 2]) -> (Vec3, f64) {
+        let to_center = self.center - origin;
+        let distance_squared = to_center.length_squared();
+        let radius_squared = self.radius * self.radius;
+
+        if distance_squared <= radius_squared {
+            let point = self.center + self.radius * uniform_sphere(u);
+            let direction = (point - origin).unit_vector();
+            return (direction, self.direction_pdf(origin, point));
+        }
+
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        let direction = Onb::from_w(to_center).to_world(uniform_cone(u, cos_theta_max));
+        (direction, uniform_cone_pdf(cos_theta_max))
+    }
+
+    // Solid angle pdf of sample_direction for the direction from origin to a point
+    // of the surface
+    pub fn direction_pdf(&self, origin: Point3, point: Point3) -> f64 {
+        let distance_squared = (self.center - origin).length_squared();
+        let radius_squared = self.radius * self.radius;
+
+        if distance_squared <= radius_squared {
+            let to_point = point - origin;
+            let normal = (point - self.center) / self.radius;
+            let cosine = dot(to_point.unit_vector(), normal).abs();
+            let area = 4.0 * PI * radius_squared;
+            return to_point.length_squared() / (cosine * area);
+        }
+
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        uniform_cone_pdf(cos_theta_max)
+    }
+
+    // Longitude and latitude of a point on the unit sphere, both scaled to [0, 1].
+    // u starts at -x and goes around the y axis, v goes from -y up to +y.
+    fn uv(p: &Point3) -> (f64, f64) {
+        let theta = (-p.y).acos();
+        let phi = (-p.z).atan2(p.x) + PI;
+        (phi / (2.0 * PI), theta / PI)
+    }
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let shifted_center = ray.origin() - self.center;
-        let a = ray.direction().length_squared();
-        let half_b = dot(shifted_center, ray.direction());
-        let c = shifted_center.length_squared() - self.radius * self.radius;
-        let discriminant = half_b * half_b - a * c;
-
-        if discriminant < 0.0 {
-            return None;
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        hit_sphere(self.center, self.radius, &self.material, ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(sphere_bounding_box(self.center, self.radius))
+    }
+}
+
+// Sphere moving in a straight line from center0 at time 0 to center1 at time 1
+pub struct MovingSphere {
+    pub center0: Point3,
+    pub center1: Point3,
+    pub radius: f64,
+    pub material: Materials,
+}
+
+impl MovingSphere {
+    pub fn new(center0: Point3, center1: Point3, radius: f64, material: Materials) -> Self {
+        MovingSphere {
+            center0,
+            center1,
+            radius,
+            material,
         }
+    }
 
-        let sqrt_d = discriminant.sqrt();
-        let root = (-half_b - sqrt_d) / a;
+    // The sphere rests at its end points outside of the frame
+    pub fn center(&self, time: f64) -> Point3 {
+        self.center0 + time.clamp(0.0, 1.0) * (self.center1 - self.center0)
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let center = self.center(ray.time());
+        hit_sphere(center, self.radius, &self.material, ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let start = sphere_bounding_box(self.center0, self.radius);
+        let end = sphere_bounding_box(self.center1, self.radius);
+        Some(Aabb::surrounding_box(&start, &end))
+    }
+}
+
+fn hit_sphere<'a>(
+    center: Point3,
+    radius: f64,
+    material: &'a Materials,
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<HitRecord<'a>> {
+    let shifted_center = ray.origin() - center;
+    let a = ray.direction().length_squared();
+    let half_b = dot(shifted_center, ray.direction());
+    let c = shifted_center.length_squared() - radius * radius;
+    let discriminant = half_b * half_b - a * c;
 
-        // First root is not in stated range
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let sqrt_d = discriminant.sqrt();
+    let mut root = (-half_b - sqrt_d) / a;
+
+    // First root is not in stated range
+    if root < t_min || root > t_max {
+        root = (-half_b + sqrt_d) / a;
+        // Second root is also not in stated range
         if root < t_min || root > t_max {
-            let root = (-half_b + sqrt_d) / a;
-            // Second root is also not in stated range
-            if root < t_min || root > t_max {
-                return None;
-            }
+            return None;
         }
+    }
+
+    let mut rec = HitRecord::EMPTY;
 
-        let mut rec = HitRecord::EMPTY;
+    rec.t = root;
+    rec.p = ray.at(rec.t);
 
-        rec.t = root;
-        rec.p = ray.at(rec.t);
+    let outward_normal = (rec.p - center) / radius;
+    rec.set_face_normal(ray, &outward_normal);
+    (rec.u, rec.v) = Sphere::uv(&outward_normal);
+    rec.material = material;
 
-        let outward_normal = (rec.p - self.center) / self.radius;
-        rec.set_face_normal(ray, &outward_normal);
-        rec.material = &self.material;
+    Some(rec)
+}
+
+fn sphere_bounding_box(center: Point3, radius: f64) -> Aabb {
+    let radius = Vec3::ONE * radius.abs();
+    Aabb::new(center - radius, center + radius)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::EmptyMaterial;
+
+    fn unit_sphere() -> Sphere {
+        Sphere::new(
+            Point3::ZERO,
+            1.0,
+            Materials::EmptyMaterial(EmptyMaterial {}),
+        )
+    }
+
+    #[test]
+    fn ray_from_inside_hits_the_far_side() {
+        let ray = Ray::new(Point3::ZERO, Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let sphere = unit_sphere();
+        let rec = sphere.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-12, "{}", rec.t);
+        assert!(!rec.front_face);
+    }
+
+    #[test]
+    fn ray_from_outside_hits_the_near_side() {
+        let ray = Ray::new(Point3::new(-3.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let sphere = unit_sphere();
+        let rec = sphere.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-12, "{}", rec.t);
+        assert!(rec.front_face);
+    }
+
+    #[test]
+    fn moving_center_is_interpolated_over_the_frame() {
+        let sphere = MovingSphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(2.0, 4.0, -6.0),
+            1.0,
+            Materials::EmptyMaterial(EmptyMaterial {}),
+        );
+        let cases = [
+            (0.0, (0.0, 0.0, 0.0)),
+            (0.25, (0.5, 1.0, -1.5)),
+            (0.5, (1.0, 2.0, -3.0)),
+            (1.0, (2.0, 4.0, -6.0)),
+            // outside of the frame the sphere rests at its end points
+            (-1.0, (0.0, 0.0, 0.0)),
+            (2.0, (2.0, 4.0, -6.0)),
+        ];
+        for (time, expected) in cases {
+            let center = sphere.center(time);
+            assert_eq!((center.x, center.y, center.z), expected, "time {time}");
+        }
+    }
 
-        Some(rec)
+    #[test]
+    fn moving_sphere_is_hit_where_it_is_at_the_ray_time() {
+        let sphere = MovingSphere::new(
+            Point3::ZERO,
+            Point3::new(0.0, 4.0, 0.0),
+            1.0,
+            Materials::EmptyMaterial(EmptyMaterial {}),
+        );
+        let ray = |time| Ray::new(Point3::new(-3.0, 2.0, 0.0), Vec3::new(1.0, 0.0, 0.0), time);
+        let rec = sphere.hit(&ray(0.5), 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-12, "{}", rec.t);
+        assert!(sphere.hit(&ray(0.0), 0.001, f64::INFINITY).is_none());
     }
 }
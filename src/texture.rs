@@ -0,0 +1,369 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::noise::{Noise, NoiseKind, Worley};
+use crate::{Color, Point3};
+
+// Every kind of texture a material can be colored with
+#[derive(Clone)]
+pub enum Textures {
+    Solid(SolidColor),
+    Checker(CheckerTexture),
+    Image(ImageTexture),
+    Noise(NoiseTexture),
+}
+
+impl Texture for Textures {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        match self {
+            Textures::Solid(solid) => solid.value(u, v, p),
+            Textures::Checker(checker) => checker.value(u, v, p),
+            Textures::Image(image) => image.value(u, v, p),
+            Textures::Noise(noise) => noise.value(u, v, p),
+        }
+    }
+}
+
+impl From<Color> for Textures {
+    fn from(color: Color) -> Self {
+        Textures::Solid(SolidColor::new(color))
+    }
+}
+
+impl From<CheckerTexture> for Textures {
+    fn from(checker: CheckerTexture) -> Self {
+        Textures::Checker(checker)
+    }
+}
+
+impl From<ImageTexture> for Textures {
+    fn from(image: ImageTexture) -> Self {
+        Textures::Image(image)
+    }
+}
+
+impl From<NoiseTexture> for Textures {
+    fn from(noise: NoiseTexture) -> Self {
+        Textures::Noise(noise)
+    }
+}
+
+pub trait Texture {
+    // Color at the surface coordinates (u, v) of the hit point p
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+}
+
+#[derive(Clone)]
+pub struct SolidColor {
+    color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> Self {
+        SolidColor { color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        self.color
+    }
+}
+
+// Alternates between two textures in cubes of the given size, the pattern
+// lives in space so it does not depend on the surface coordinates
+#[derive(Clone)]
+pub struct CheckerTexture {
+    inverse_scale: f64,
+    even: Box<Textures>,
+    odd: Box<Textures>,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f64, even: impl Into<Textures>, odd: impl Into<Textures>) -> Self {
+        CheckerTexture {
+            inverse_scale: 1.0 / scale,
+            even: Box::new(even.into()),
+            odd: Box::new(odd.into()),
+        }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let x = (self.inverse_scale * p.x).floor() as i64;
+        let y = (self.inverse_scale * p.y).floor() as i64;
+        let z = (self.inverse_scale * p.z).floor() as i64;
+
+        if (x + y + z) % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+// How texture coordinates outside of [0, 1] are mapped back onto the image
+#[derive(Copy, Clone, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WrapMode {
+    #[default]
+    Repeat,
+    Mirror,
+    Clamp,
+}
+
+impl WrapMode {
+    // Maps a pixel index that can lie outside of the image into 0..size
+    fn apply(self, index: i64, size: usize) -> usize {
+        let size = size as i64;
+        let index = match self {
+            WrapMode::Repeat => index.rem_euclid(size),
+            WrapMode::Mirror => {
+                let period = index.rem_euclid(2 * size);
+                if period < size {
+                    period
+                } else {
+                    2 * size - 1 - period
+                }
+            }
+            WrapMode::Clamp => index.clamp(0, size - 1),
+        };
+        index as usize
+    }
+}
+
+// Decoded pixels, shared by every texture made from the same file
+struct ImageData {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl ImageData {
+    fn pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+}
+
+// Image mapped onto the surface coordinates, (0, 0) is the bottom left corner
+#[derive(Clone)]
+pub struct ImageTexture {
+    data: Arc<ImageData>,
+    wrap: WrapMode,
+}
+
+impl ImageTexture {
+    pub fn load(path: &Path, wrap: WrapMode) -> Result<Self, image::ImageError> {
+        let image = image::open(path)?.into_rgb8();
+        let (width, height) = image.dimensions();
+
+        // Files are stored with the same gamma of 2 the renderer writes images with
+        let pixels = image
+            .pixels()
+            .map(|pixel| {
+                let [r, g, b] = pixel.0.map(|channel| {
+                    let value = channel as f64 / 255.0;
+                    value * value
+                });
+                Color::new(r, g, b)
+            })
+            .collect();
+
+        Ok(ImageTexture {
+            data: Arc::new(ImageData {
+                width: width as usize,
+                height: height as usize,
+                pixels,
+            }),
+            wrap,
+        })
+    }
+}
+
+impl Texture for ImageTexture {
+    // Bilinear interpolation between the four pixels around (u, v)
+    fn value(&self, u: f64, v: f64, _p: &Point3) -> Color {
+        let data = &self.data;
+        // Cyan makes a texture without pixels easy to spot
+        if data.pixels.is_empty() {
+            return Color::new(0.0, 1.0, 1.0);
+        }
+
+        // Pixel centers sit at half integer coordinates, image rows go downwards
+        let x = u * data.width as f64 - 0.5;
+        let y = (1.0 - v) * data.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+
+        let column = |offset: i64| self.wrap.apply(x0 as i64 + offset, data.width);
+        let row = |offset: i64| self.wrap.apply(y0 as i64 + offset, data.height);
+        let (left, right) = (column(0), column(1));
+        let (top, bottom) = (row(0), row(1));
+
+        let upper = (1.0 - tx) * data.pixel(left, top) + tx * data.pixel(right, top);
+        let lower = (1.0 - tx) * data.pixel(left, bottom) + tx * data.pixel(right, bottom);
+        (1.0 - ty) * upper + ty * lower
+    }
+}
+
+// Shapes a noise value into [0, 1], which blends the two colors of a NoiseTexture
+#[derive(Copy, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoisePattern {
+    Fbm,
+    Turbulence,
+    // veins across the z axis, bent by turbulence
+    Marble,
+    // rings around the y axis, bent by fbm
+    Wood,
+    // distance to the closest Worley feature point
+    Cells,
+    // dark lines where two Worley cells meet
+    CellBorders,
+}
+
+// Procedural texture, the pattern is evaluated at the hit point scaled by scale
+#[derive(Clone)]
+pub struct NoiseTexture {
+    noise: Noise,
+    worley: Worley,
+    pattern: NoisePattern,
+    scale: f64,
+    octaves: u32,
+    distortion: f64,
+    low: Color,
+    high: Color,
+}
+
+impl NoiseTexture {
+    // Gradient noise of the given kind and Worley noise are both seeded with seed
+    pub fn new(pattern: NoisePattern, kind: NoiseKind, seed: u64, scale: f64) -> Self {
+        NoiseTexture {
+            noise: Noise::new(kind, seed),
+            worley: Worley::new(seed),
+            pattern,
+            scale,
+            octaves: 7,
+            distortion: 10.0,
+            low: Color::ZERO,
+            high: Color::ONE,
+        }
+    }
+
+    pub fn colors(self, low: Color, high: Color) -> Self {
+        NoiseTexture { low, high, ..self }
+    }
+
+    pub fn octaves(self, octaves: u32) -> Self {
+        NoiseTexture { octaves, ..self }
+    }
+
+    // How strongly marble veins and wood rings are bent
+    pub fn distortion(self, distortion: f64) -> Self {
+        NoiseTexture { distortion, ..self }
+    }
+
+    fn intensity(&self, point: &Point3) -> f64 {
+        let p = self.scale * *point;
+        match self.pattern {
+            NoisePattern::Fbm => 0.5 * (1.0 + self.noise.fbm(&p, self.octaves)),
+            NoisePattern::Turbulence => self.noise.turbulence(&p, self.octaves),
+            // The scale only sets how close the veins are, the turbulence keeps its size
+            NoisePattern::Marble => {
+                let phase = p.z + self.distortion * self.noise.turbulence(point, self.octaves);
+                0.5 * (1.0 + phase.sin())
+            }
+            NoisePattern::Wood => {
+                let radius = (p.x * p.x + p.z * p.z).sqrt();
+                let rings = radius + 0.1 * self.distortion * self.noise.fbm(&p, self.octaves);
+                rings - rings.floor()
+            }
+            NoisePattern::Cells => self.worley.distances(&p).0,
+            NoisePattern::CellBorders => {
+                let (closest, second) = self.worley.distances(&p);
+                second - closest
+            }
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let t = self.intensity(p).clamp(0.0, 1.0);
+        (1.0 - t) * self.low + t * self.high
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Color = Color::ZERO;
+    const WHITE: Color = Color::ONE;
+
+    fn image(width: usize, height: usize, pixels: Vec<Color>, wrap: WrapMode) -> ImageTexture {
+        ImageTexture {
+            data: Arc::new(ImageData {
+                width,
+                height,
+                pixels,
+            }),
+            wrap,
+        }
+    }
+
+    fn assert_gray(color: Color, expected: f64) {
+        for channel in [color.x, color.y, color.z] {
+            assert!(
+                (channel - expected).abs() < 1e-12,
+                "{channel} != {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn checker_alternates_with_the_parity_of_the_cell() {
+        let checker = CheckerTexture::new(1.0, BLACK, WHITE);
+        let value = |x, y, z| checker.value(0.0, 0.0, &Point3::new(x, y, z)).x;
+        assert_eq!(value(0.5, 0.5, 0.5), 0.0);
+        assert_eq!(value(1.5, 0.5, 0.5), 1.0);
+        assert_eq!(value(1.5, 1.5, 0.5), 0.0);
+        assert_eq!(value(1.5, 1.5, 1.5), 1.0);
+        // cells below zero continue the pattern
+        assert_eq!(value(-0.5, 0.5, 0.5), 1.0);
+        assert_eq!(value(-0.5, -0.5, 0.5), 0.0);
+    }
+
+    #[test]
+    fn wrap_modes_outside_of_the_unit_square() {
+        // Pixel centers of the second copy of the image along u and v
+        let expected = [
+            (WrapMode::Repeat, [0.0, 1.0]),
+            (WrapMode::Mirror, [1.0, 0.0]),
+            (WrapMode::Clamp, [1.0, 1.0]),
+        ];
+        for (wrap, [first, second]) in expected {
+            // black on the left, white on the right
+            let wide = image(2, 1, vec![BLACK, WHITE], wrap);
+            assert_gray(wide.value(1.25, 0.5, &Point3::ZERO), first);
+            assert_gray(wide.value(1.75, 0.5, &Point3::ZERO), second);
+
+            // black on top, white at the bottom, v grows upwards
+            let tall = image(1, 2, vec![BLACK, WHITE], wrap);
+            assert_gray(tall.value(0.5, -0.25, &Point3::ZERO), first);
+            assert_gray(tall.value(0.5, -0.75, &Point3::ZERO), second);
+        }
+    }
+
+    #[test]
+    fn image_is_interpolated_between_texel_centers() {
+        let texture = image(2, 1, vec![BLACK, WHITE], WrapMode::Clamp);
+        assert_gray(texture.value(0.25, 0.5, &Point3::ZERO), 0.0);
+        assert_gray(texture.value(0.375, 0.5, &Point3::ZERO), 0.25);
+        assert_gray(texture.value(0.5, 0.5, &Point3::ZERO), 0.5);
+        assert_gray(texture.value(0.75, 0.5, &Point3::ZERO), 1.0);
+    }
+}
@@ -0,0 +1,143 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+
+#[derive(Copy, Clone, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum TileOrder {
+    // Outwards from the center of the image, where the subject usually is
+    Spiral,
+    // Along a Hilbert curve, consecutive tiles are neighbours and share cached geometry
+    Hilbert,
+}
+
+// Rectangle of pixels, rows count from the top of the image
+#[derive(Copy, Clone)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Tiles covering the image in the order they should be rendered, the ones at the
+// right and bottom edges may be smaller
+pub fn tiles(image_width: u32, image_height: u32, tile_size: u32, order: TileOrder) -> Vec<Tile> {
+    let columns = image_width.div_ceil(tile_size);
+    let rows = image_height.div_ceil(tile_size);
+
+    let cells = match order {
+        TileOrder::Spiral => spiral(columns, rows),
+        TileOrder::Hilbert => hilbert(columns, rows),
+    };
+    cells
+        .into_iter()
+        .map(|(column, row)| {
+            let x = column * tile_size;
+            let y = row * tile_size;
+            Tile {
+                x,
+                y,
+                width: tile_size.min(image_width - x),
+                height: tile_size.min(image_height - y),
+            }
+        })
+        .collect()
+}
+
+// Rings around the center cell, each turn of the walk is one longer every second time
+fn spiral(columns: u32, rows: u32) -> Vec<(u32, u32)> {
+    let count = columns as usize * rows as usize;
+    let mut cells = Vec::with_capacity(count);
+    let inside = |x: i64, y: i64| (0..columns as i64).contains(&x) && (0..rows as i64).contains(&y);
+
+    let (mut x, mut y) = ((columns as i64 - 1) / 2, (rows as i64 - 1) / 2);
+    cells.push((x as u32, y as u32));
+
+    let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let mut direction = 0;
+    let mut length = 1;
+    while cells.len() < count {
+        for _ in 0..2 {
+            let (dx, dy) = directions[direction];
+            for _ in 0..length {
+                x += dx;
+                y += dy;
+                if inside(x, y) {
+                    cells.push((x as u32, y as u32));
+                }
+            }
+            direction = (direction + 1) % 4;
+        }
+        length += 1;
+    }
+    cells
+}
+
+// Curve through a power of two square around the grid, cells outside are skipped
+fn hilbert(columns: u32, rows: u32) -> Vec<(u32, u32)> {
+    let size = columns.max(rows).next_power_of_two();
+    (0..size as u64 * size as u64)
+        .map(|d| hilbert_cell(size, d))
+        .filter(|&(x, y)| x < columns && y < rows)
+        .collect()
+}
+
+// Cell at distance d along the curve, built up from the smallest squares
+fn hilbert_cell(size: u32, mut d: u64) -> (u32, u32) {
+    let (mut x, mut y) = (0, 0);
+    let mut s = 1;
+    while s < size {
+        let rx = (1 & (d / 2)) as u32;
+        let ry = (1 & (d ^ rx as u64)) as u32;
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - x;
+                y = s - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        x += s * rx;
+        y += s * ry;
+        d /= 4;
+        s *= 2;
+    }
+    (x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_pixel_is_covered_once() {
+        let (width, height) = (37, 23);
+        for order in [TileOrder::Spiral, TileOrder::Hilbert] {
+            let mut covered = vec![0; (width * height) as usize];
+            for tile in tiles(width, height, 8, order) {
+                assert!(tile.width > 0 && tile.height > 0);
+                for y in tile.y..tile.y + tile.height {
+                    for x in tile.x..tile.x + tile.width {
+                        covered[(y * width + x) as usize] += 1;
+                    }
+                }
+            }
+            assert!(covered.iter().all(|&count| count == 1));
+        }
+    }
+
+    #[test]
+    fn spiral_starts_at_the_center() {
+        let first = tiles(37, 23, 8, TileOrder::Spiral)[0];
+        assert_eq!((first.x, first.y), (16, 8));
+    }
+
+    #[test]
+    fn hilbert_steps_to_neighbouring_tiles() {
+        let tiles = tiles(64, 64, 8, TileOrder::Hilbert);
+        assert_eq!(tiles.len(), 64);
+        for pair in tiles.windows(2) {
+            let distance = pair[0].x.abs_diff(pair[1].x) + pair[0].y.abs_diff(pair[1].y);
+            assert_eq!(distance, 8);
+        }
+    }
+}
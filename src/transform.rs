@@ -0,0 +1,521 @@
+use std::ops::Mul;
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable, Hittables};
+use crate::{Point3, Ray, Vec3};
+
+// Row-major affine matrix, points are column vectors with w = 1
+#[derive(Copy, Clone)]
+pub struct Matrix4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Matrix4 {
+    pub const IDENTITY: Matrix4 = Matrix4 {
+        m: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    };
+
+    pub fn translation(offset: Vec3) -> Self {
+        let mut matrix = Matrix4::IDENTITY;
+        matrix.m[0][3] = offset.x;
+        matrix.m[1][3] = offset.y;
+        matrix.m[2][3] = offset.z;
+        matrix
+    }
+
+    pub fn scaling(factors: Vec3) -> Self {
+        let mut matrix = Matrix4::IDENTITY;
+        matrix.m[0][0] = factors.x;
+        matrix.m[1][1] = factors.y;
+        matrix.m[2][2] = factors.z;
+        matrix
+    }
+
+    // Counterclockwise rotation around the axis when looking against it (Rodrigues' formula)
+    pub fn rotation(axis: Vec3, degrees: f64) -> Self {
+        let a = axis.unit_vector();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let k = 1.0 - cos;
+
+        Matrix4 {
+            m: [
+                [
+                    a.x * a.x * k + cos,
+                    a.x * a.y * k - a.z * sin,
+                    a.x * a.z * k + a.y * sin,
+                    0.0,
+                ],
+                [
+                    a.y * a.x * k + a.z * sin,
+                    a.y * a.y * k + cos,
+                    a.y * a.z * k - a.x * sin,
+                    0.0,
+                ],
+                [
+                    a.z * a.x * k - a.y * sin,
+                    a.z * a.y * k + a.x * sin,
+                    a.z * a.z * k + cos,
+                    0.0,
+                ],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut matrix = Matrix4::IDENTITY;
+        for (i, row) in matrix.m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        matrix
+    }
+
+    // Gauss-Jordan elimination with partial pivoting, None for singular matrices
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.m;
+        let mut inverse = Matrix4::IDENTITY.m;
+
+        for column in 0..4 {
+            let pivot = (column..4)
+                .max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs()))
+                .unwrap();
+            if a[pivot][column].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(column, pivot);
+            inverse.swap(column, pivot);
+
+            let scale = 1.0 / a[column][column];
+            for j in 0..4 {
+                a[column][j] *= scale;
+                inverse[column][j] *= scale;
+            }
+
+            for row in 0..4 {
+                if row == column {
+                    continue;
+                }
+                let factor = a[row][column];
+                for j in 0..4 {
+                    a[row][j] -= factor * a[column][j];
+                    inverse[row][j] -= factor * inverse[column][j];
+                }
+            }
+        }
+
+        Some(Matrix4 { m: inverse })
+    }
+
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        let m = &self.m;
+        let x = m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3];
+        let y = m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3];
+        let z = m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3];
+        let w = m[3][0] * p.x + m[3][1] * p.y + m[3][2] * p.z + m[3][3];
+        if w == 1.0 {
+            Point3::new(x, y, z)
+        } else {
+            Point3::new(x, y, z) / w
+        }
+    }
+
+    // Directions ignore the translation
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+}
+
+impl Mul for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, rhs: Matrix4) -> Self::Output {
+        let mut matrix = Matrix4 { m: [[0.0; 4]; 4] };
+        for i in 0..4 {
+            for j in 0..4 {
+                matrix.m[i][j] = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        matrix
+    }
+}
+
+// Object to world matrix together with its inverse
+#[derive(Copy, Clone)]
+pub struct Transform {
+    pub matrix: Matrix4,
+    pub inverse: Matrix4,
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        matrix: Matrix4::IDENTITY,
+        inverse: Matrix4::IDENTITY,
+    };
+
+    pub fn from_matrix(matrix: Matrix4) -> Option<Self> {
+        Some(Transform {
+            matrix,
+            inverse: matrix.inverse()?,
+        })
+    }
+
+    // Applies other after self
+    pub fn then(self, other: Transform) -> Self {
+        Transform {
+            matrix: other.matrix * self.matrix,
+            inverse: self.inverse * other.inverse,
+        }
+    }
+
+    pub fn translate(self, offset: Vec3) -> Self {
+        self.then(Transform {
+            matrix: Matrix4::translation(offset),
+            inverse: Matrix4::translation(-offset),
+        })
+    }
+
+    // Zero factors collapse the object and are not allowed
+    pub fn scale(self, factors: Vec3) -> Self {
+        self.then(Transform {
+            matrix: Matrix4::scaling(factors),
+            inverse: Matrix4::scaling(Vec3::new(1.0 / factors.x, 1.0 / factors.y, 1.0 / factors.z)),
+        })
+    }
+
+    pub fn rotate(self, axis: Vec3, degrees: f64) -> Self {
+        let rotation = Matrix4::rotation(axis, degrees);
+        self.then(Transform {
+            matrix: rotation,
+            inverse: rotation.transpose(),
+        })
+    }
+
+    // Normals stay perpendicular to the surface with the inverse transpose
+    pub fn transform_normal(&self, n: Vec3) -> Vec3 {
+        self.inverse.transpose().transform_vector(n)
+    }
+
+    pub fn transform_box(&self, bounds: &Aabb) -> Aabb {
+        let mut output_box = Aabb::EMPTY;
+        for i in 0..8 {
+            let corner = Point3::new(
+                if i & 1 == 0 {
+                    bounds.minimum.x
+                } else {
+                    bounds.maximum.x
+                },
+                if i & 2 == 0 {
+                    bounds.minimum.y
+                } else {
+                    bounds.maximum.y
+                },
+                if i & 4 == 0 {
+                    bounds.minimum.z
+                } else {
+                    bounds.maximum.z
+                },
+            );
+            output_box = output_box.surrounding_point(self.matrix.transform_point(corner));
+        }
+        output_box
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum MotionStep {
+    Translate(Vec3),
+    Rotate { axis: Vec3, degrees: f64 },
+    // factors must be positive
+    Scale(Vec3),
+}
+
+// Steps that are applied gradually during the frame, not at all at time 0 and
+// completely at time 1
+pub struct Motion {
+    steps: Vec<MotionStep>,
+}
+
+impl Motion {
+    pub fn new(steps: Vec<MotionStep>) -> Self {
+        Motion { steps }
+    }
+
+    // Upper bound of how far a point within radius of the origin moves per unit of
+    // time. Steps are bounded one after another with the distance from the origin
+    // they can reach, rotations move points along arcs of that radius.
+    pub fn max_speed(&self, radius: f64) -> f64 {
+        let mut radius = radius;
+        let mut speed = 0.0;
+        for step in &self.steps {
+            match *step {
+                MotionStep::Translate(offset) => {
+                    speed += offset.length();
+                    radius += offset.length();
+                }
+                MotionStep::Rotate { degrees, .. } => speed += degrees.to_radians().abs() * radius,
+                // d/ds f^s = ln(f) f^s, and f^s stays between 1 and f
+                MotionStep::Scale(factors) => {
+                    let largest = factors.x.max(factors.y).max(factors.z).max(1.0);
+                    let rate = [factors.x, factors.y, factors.z]
+                        .map(|factor| factor.ln().abs())
+                        .into_iter()
+                        .fold(0.0, f64::max);
+                    speed = largest * (speed + rate * radius);
+                    radius *= largest;
+                }
+            }
+        }
+        speed
+    }
+
+    pub fn at(&self, time: f64) -> Transform {
+        let s = time.clamp(0.0, 1.0);
+        self.steps
+            .iter()
+            .fold(Transform::IDENTITY, |transform, step| match *step {
+                MotionStep::Translate(offset) => transform.translate(s * offset),
+                MotionStep::Rotate { axis, degrees } => transform.rotate(axis, s * degrees),
+                // Geometric interpolation keeps the factors away from zero
+                MotionStep::Scale(factors) => transform.scale(Vec3::new(
+                    factors.x.powf(s),
+                    factors.y.powf(s),
+                    factors.z.powf(s),
+                )),
+            })
+    }
+}
+
+// Places a shared object in the world, any number of instances can use the same object
+pub struct Instance {
+    object: Arc<Hittables>,
+    transform: Transform,
+    // applied in object space, before the transform
+    motion: Option<Motion>,
+    bounds: Option<Aabb>,
+}
+
+impl Instance {
+    pub fn new(object: Arc<Hittables>, transform: Transform) -> Self {
+        let bounds = object
+            .bounding_box()
+            .map(|bounds| transform.transform_box(&bounds));
+        Instance {
+            object,
+            transform,
+            motion: None,
+            bounds,
+        }
+    }
+
+    pub fn animated(object: Arc<Hittables>, transform: Transform, motion: Motion) -> Self {
+        // Boxes at the sampled times miss where points go in between, most of all on
+        // the arcs of fast rotations. No point is further from the closest sample
+        // than it moves in half a time step, the padding covers that distance.
+        const SAMPLES: usize = 64;
+        let bounds = object.bounding_box().map(|bounds| {
+            let swept = (0..=SAMPLES)
+                .map(|i| {
+                    let time = i as f64 / SAMPLES as f64;
+                    motion.at(time).then(transform).transform_box(&bounds)
+                })
+                .fold(Aabb::EMPTY, |a, b| Aabb::surrounding_box(&a, &b));
+
+            // Every point of the object is within radius of its origin, and the
+            // transform lengthens distances by at most stretch
+            let radius = bounds.centroid().length() + 0.5 * bounds.diagonal().length();
+            let stretch = transform.matrix.m[..3]
+                .iter()
+                .flat_map(|row| &row[..3])
+                .map(|value| value * value)
+                .sum::<f64>()
+                .sqrt();
+            let step = stretch * motion.max_speed(radius) / SAMPLES as f64;
+            swept.expanded(step / 2.0)
+        });
+        Instance {
+            object,
+            transform,
+            motion: Some(motion),
+            bounds,
+        }
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let transform = match &self.motion {
+            Some(motion) => motion.at(r.time()).then(self.transform),
+            None => self.transform,
+        };
+
+        // The direction is not normalized, so t is the same in both spaces
+        let object_ray = Ray::new(
+            transform.inverse.transform_point(r.origin()),
+            transform.inverse.transform_vector(r.direction()),
+            r.time(),
+        );
+
+        let mut rec = self.object.hit(&object_ray, t_min, t_max)?;
+
+        // The normal already faces the ray and keeps doing so after the transform
+        rec.p = transform.matrix.transform_point(rec.p);
+        rec.normal = transform.transform_normal(rec.normal).unit_vector();
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bounds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{EmptyMaterial, Materials};
+    use crate::sphere::Sphere;
+    use crate::vec3::dot;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!(
+            (a - b).length() < 1e-9,
+            "({}, {}, {}) != ({}, {}, {})",
+            a.x,
+            a.y,
+            a.z,
+            b.x,
+            b.y,
+            b.z
+        );
+    }
+
+    fn trs() -> Transform {
+        Transform::IDENTITY
+            .scale(Vec3::new(2.0, 0.5, 3.0))
+            .rotate(Vec3::new(1.0, 2.0, -1.0), 37.0)
+            .translate(Vec3::new(4.0, -1.0, 2.0))
+    }
+
+    #[test]
+    fn inverse_undoes_the_matrix() {
+        let matrix = trs().matrix;
+        let product = matrix * matrix.inverse().unwrap();
+        for (i, row) in product.m.iter().enumerate() {
+            for (j, &value) in row.iter().enumerate() {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((value - expected).abs() < 1e-12, "[{i}][{j}] = {value}");
+            }
+        }
+        // and agrees with the inverse built step by step
+        let inverse = matrix.inverse().unwrap();
+        for (row, expected) in inverse.m.iter().zip(trs().inverse.m) {
+            for (&value, expected) in row.iter().zip(expected) {
+                assert!((value - expected).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        assert!(Matrix4::scaling(Vec3::new(1.0, 0.0, 1.0))
+            .inverse()
+            .is_none());
+    }
+
+    #[test]
+    fn normal_stays_perpendicular_under_non_uniform_scale() {
+        let transform = trs();
+        let normal = Vec3::new(1.0, 1.0, 0.0);
+        for tangent in [Vec3::new(1.0, -1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)] {
+            let product = dot(
+                transform.transform_normal(normal),
+                transform.matrix.transform_vector(tangent),
+            );
+            assert!(product.abs() < 1e-12, "{product}");
+        }
+    }
+
+    #[test]
+    fn motion_is_interpolated() {
+        let motion = Motion::new(vec![
+            MotionStep::Scale(Vec3::new(4.0, 1.0, 1.0)),
+            MotionStep::Translate(Vec3::new(2.0, 0.0, 0.0)),
+        ]);
+        let p = Point3::new(1.0, 1.0, 1.0);
+        assert_close(motion.at(0.0).matrix.transform_point(p), p);
+        assert_close(
+            motion.at(0.5).matrix.transform_point(p),
+            Point3::new(3.0, 1.0, 1.0),
+        );
+        assert_close(
+            motion.at(1.0).matrix.transform_point(p),
+            Point3::new(6.0, 1.0, 1.0),
+        );
+    }
+
+    #[test]
+    fn fast_rotation_stays_in_the_bounds_between_samples() {
+        let sphere = Sphere::new(
+            Point3::new(5.0, 0.0, 0.0),
+            0.1,
+            Materials::EmptyMaterial(EmptyMaterial {}),
+        );
+        // 120 degrees between the 64 sampled times, which all land at three angles
+        let degrees = 120.0 * 64.0;
+        let motion = Motion::new(vec![MotionStep::Rotate {
+            axis: Vec3::y(1.0),
+            degrees,
+        }]);
+        let instance = Instance::animated(Arc::new(sphere.into()), Transform::IDENTITY, motion);
+        let bounds = instance.bounding_box().unwrap();
+
+        // Halfway between two samples the sphere is on the opposite side, at x = -5
+        let time = 1.5 / 64.0;
+        let ray = Ray::new(Point3::new(-5.0, 10.0, 0.0), Vec3::y(-1.0), time);
+        let rec = instance.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert_close(rec.p, Point3::new(-5.0, 0.1, 0.0));
+        let inverse = Vec3::new(f64::INFINITY, -1.0, f64::INFINITY);
+        assert!(bounds.hit_with_inverse(ray.origin(), inverse, 0.001, f64::INFINITY));
+        assert!(bounds.minimum.x <= -5.1);
+    }
+
+    #[test]
+    fn instanced_sphere_is_hit_where_expected() {
+        let sphere = Sphere::new(
+            Point3::ZERO,
+            1.0,
+            Materials::EmptyMaterial(EmptyMaterial {}),
+        );
+        // Ellipsoid with half axes 2, 1 and 1 around (3, 0, 0)
+        let transform = Transform::IDENTITY
+            .scale(Vec3::new(2.0, 1.0, 1.0))
+            .translate(Vec3::new(3.0, 0.0, 0.0));
+        let instance = Instance::new(Arc::new(sphere.into()), transform);
+
+        let bounds = instance.bounding_box().unwrap();
+        assert_close(bounds.minimum, Point3::new(1.0, -1.0, -1.0));
+        assert_close(bounds.maximum, Point3::new(5.0, 1.0, 1.0));
+
+        // Straight down onto the point with x = 3 + sqrt(2), where x^2 / 4 + y^2 = 1
+        // gives y = sqrt(1/2) and the gradient points along (1, 2, 0)
+        let x = 3.0 + 2f64.sqrt();
+        let ray = Ray::new(Point3::new(x, 10.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        let rec = instance.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - (10.0 - 0.5f64.sqrt())).abs() < 1e-9, "{}", rec.t);
+        assert_close(rec.p, Point3::new(x, 0.5f64.sqrt(), 0.0));
+        assert_close(rec.normal, Vec3::new(1.0, 2.0, 0.0).unit_vector());
+    }
+}
@@ -0,0 +1,281 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Materials;
+use crate::vec3::dot;
+use crate::{Point3, Ray, Vec3};
+
+pub struct Triangle {
+    pub vertices: [Point3; 3],
+    // per-vertex shading normals, the geometric normal is used without them
+    pub normals: Option<[Vec3; 3]>,
+    pub uvs: [(f64, f64); 3],
+    pub material: Materials,
+}
+
+impl Triangle {
+    pub const DEFAULT_UVS: [(f64, f64); 3] = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)];
+
+    pub fn new(vertices: [Point3; 3], material: Materials) -> Self {
+        Triangle {
+            vertices,
+            normals: None,
+            uvs: Triangle::DEFAULT_UVS,
+            material,
+        }
+    }
+
+    pub fn with_vertex_data(
+        vertices: [Point3; 3],
+        normals: Option<[Vec3; 3]>,
+        uvs: [(f64, f64); 3],
+        material: Materials,
+    ) -> Self {
+        Triangle {
+            vertices,
+            normals,
+            uvs,
+            material,
+        }
+    }
+}
+
+// Watertight ray-triangle intersection (Woop, Benthin, Wald 2013).
+// Returns t and the barycentric coordinates of the hit point.
+pub fn intersect_triangle(
+    vertices: &[Point3; 3],
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, Vec3)> {
+    let direction = ray.direction();
+
+    // Permute the axes so the ray direction is largest along z
+    let kz = Vec3::new(direction.x.abs(), direction.y.abs(), direction.z.abs()).max_dimension();
+    let kx = (kz + 1) % 3;
+    let ky = (kx + 1) % 3;
+    let permute = |v: Vec3| Vec3::new(v[kx], v[ky], v[kz]);
+
+    let d = permute(direction);
+    let mut p0 = permute(vertices[0] - ray.origin());
+    let mut p1 = permute(vertices[1] - ray.origin());
+    let mut p2 = permute(vertices[2] - ray.origin());
+
+    // Shear so the ray points along +z
+    let shear_x = -d.x / d.z;
+    let shear_y = -d.y / d.z;
+    let shear_z = 1.0 / d.z;
+    for p in [&mut p0, &mut p1, &mut p2] {
+        p.x += shear_x * p.z;
+        p.y += shear_y * p.z;
+    }
+
+    // Edge functions, each is zero on the edge opposite to its vertex. Two triangles
+    // sharing an edge compute the same value for it with opposite signs.
+    let e0 = p1.x * p2.y - p1.y * p2.x;
+    let e1 = p2.x * p0.y - p2.y * p0.x;
+    let e2 = p0.x * p1.y - p0.y * p1.x;
+
+    if (e0 < 0.0 || e1 < 0.0 || e2 < 0.0) && (e0 > 0.0 || e1 > 0.0 || e2 > 0.0) {
+        return None;
+    }
+    let det = e0 + e1 + e2;
+    if det == 0.0 {
+        return None;
+    }
+
+    // A point exactly on an edge belongs to only one of the triangles sharing it. Seen
+    // with the orientation of det the neighbour runs along the edge the other way,
+    // so the side owning the edge is picked by its direction.
+    let owns_edge = |from: Vec3, to: Vec3| {
+        let (dx, dy) = (
+            (to.x - from.x) * det.signum(),
+            (to.y - from.y) * det.signum(),
+        );
+        dy > 0.0 || (dy == 0.0 && dx < 0.0)
+    };
+    if (e0 == 0.0 && !owns_edge(p1, p2))
+        || (e1 == 0.0 && !owns_edge(p2, p0))
+        || (e2 == 0.0 && !owns_edge(p0, p1))
+    {
+        return None;
+    }
+
+    // Compare the scaled hit distance against the range without dividing by det
+    p0.z *= shear_z;
+    p1.z *= shear_z;
+    p2.z *= shear_z;
+    let t_scaled = e0 * p0.z + e1 * p1.z + e2 * p2.z;
+    if det < 0.0 && (t_scaled >= t_min * det || t_scaled < t_max * det) {
+        return None;
+    }
+    if det > 0.0 && (t_scaled <= t_min * det || t_scaled > t_max * det) {
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+    let barycentric = Vec3::new(e0 * inv_det, e1 * inv_det, e2 * inv_det);
+    Some((t_scaled * inv_det, barycentric))
+}
+
+// Fills the parts of a triangle hit record that do not depend on where the data is stored
+pub fn triangle_hit_record<'a>(
+    ray: &Ray,
+    t: f64,
+    barycentric: Vec3,
+    vertices: &[Point3; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: &[(f64, f64); 3],
+    material: &'a Materials,
+) -> HitRecord<'a> {
+    let mut rec = HitRecord::EMPTY;
+    let (b0, b1, b2) = (barycentric.x, barycentric.y, barycentric.z);
+
+    rec.t = t;
+    rec.p = b0 * vertices[0] + b1 * vertices[1] + b2 * vertices[2];
+    rec.barycentric = barycentric;
+    rec.u = b0 * uvs[0].0 + b1 * uvs[1].0 + b2 * uvs[2].0;
+    rec.v = b0 * uvs[0].1 + b1 * uvs[1].1 + b2 * uvs[2].1;
+    rec.material = material;
+
+    let mut geometric_normal =
+        Vec3::cross(vertices[1] - vertices[0], vertices[2] - vertices[0]).unit_vector();
+
+    match normals {
+        Some(n) => {
+            let shading_normal = (b0 * n[0] + b1 * n[1] + b2 * n[2]).unit_vector();
+            // The winding order decides the geometric normal, trust the shading normal instead
+            if dot(geometric_normal, shading_normal) < 0.0 {
+                geometric_normal = -geometric_normal;
+            }
+            rec.set_face_normal(ray, &geometric_normal);
+            rec.normal = if rec.front_face {
+                shading_normal
+            } else {
+                -shading_normal
+            };
+        }
+        None => rec.set_face_normal(ray, &geometric_normal),
+    }
+
+    rec
+}
+
+pub fn triangle_bounding_box(vertices: &[Point3; 3]) -> Aabb {
+    Aabb::new(vertices[0], vertices[1]).surrounding_point(vertices[2])
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (t, barycentric) = intersect_triangle(&self.vertices, ray, t_min, t_max)?;
+        Some(triangle_hit_record(
+            ray,
+            t,
+            barycentric,
+            &self.vertices,
+            self.normals,
+            &self.uvs,
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(triangle_bounding_box(&self.vertices))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::EmptyMaterial;
+
+    fn triangle(vertices: [Point3; 3]) -> Triangle {
+        Triangle::new(vertices, Materials::EmptyMaterial(EmptyMaterial {}))
+    }
+
+    fn hits(vertices: &[Point3; 3], ray: &Ray) -> usize {
+        intersect_triangle(vertices, ray, 0.001, f64::INFINITY).is_some() as usize
+    }
+
+    // Rays from a few origins through points along the edge from a to b
+    fn rays_through_edge(a: Point3, b: Point3) -> Vec<Ray> {
+        let mut rays = Vec::new();
+        for origin in [
+            Point3::new(0.0, 0.0, 2.0),
+            Point3::new(0.3, -1.7, 1.1),
+            Point3::new(-2.0, 0.6, -1.5),
+        ] {
+            for i in 0..=32 {
+                let point = a + (i as f64 / 32.0) * (b - a);
+                rays.push(Ray::new(origin, point - origin, 0.0));
+            }
+        }
+        rays
+    }
+
+    #[test]
+    fn shared_edge_is_hit_exactly_once() {
+        let p = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(1.0, 1.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+        ];
+        // a square split along its diagonal, with both windings of the second half
+        let first = [p[0], p[1], p[2]];
+        for second in [[p[0], p[2], p[3]], [p[0], p[3], p[2]]] {
+            for ray in rays_through_edge(p[0] + 0.01 * (p[2] - p[0]), p[2] - 0.01 * (p[2] - p[0])) {
+                assert_eq!(hits(&first, &ray) + hits(&second, &ray), 1);
+            }
+        }
+
+        // an edge along an axis, between a triangle above and one below it
+        let above = [p[0], p[1], p[3]];
+        let below = [p[1], p[0], Point3::new(0.5, -1.0, 0.0)];
+        for ray in rays_through_edge(Point3::new(0.01, 0.0, 0.0), Point3::new(0.99, 0.0, 0.0)) {
+            assert_eq!(hits(&above, &ray) + hits(&below, &ray), 1);
+        }
+    }
+
+    #[test]
+    fn vertex_data_is_interpolated() {
+        let mut triangle = triangle([
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(2.0, 0.0, 0.0),
+            Point3::new(0.0, 2.0, 0.0),
+        ]);
+        triangle.uvs = [(0.1, 0.2), (0.9, 0.2), (0.1, 0.8)];
+
+        // close to the second vertex
+        let ray = Ray::new(Point3::new(2.0 - 2e-9, 1e-9, 1.0), Vec3::z(-1.0), 0.0);
+        let rec = triangle.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.barycentric - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-8);
+        assert!((rec.u - 0.9).abs() < 1e-8 && (rec.v - 0.2).abs() < 1e-8);
+
+        // the centroid
+        let ray = Ray::new(Point3::new(2.0 / 3.0, 2.0 / 3.0, 1.0), Vec3::z(-1.0), 0.0);
+        let rec = triangle.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.barycentric - Vec3::ONE / 3.0).length() < 1e-12);
+        assert!((rec.p - Point3::new(2.0 / 3.0, 2.0 / 3.0, 0.0)).length() < 1e-12);
+        assert!((rec.u - 1.1 / 3.0).abs() < 1e-12 && (rec.v - 0.4).abs() < 1e-12);
+        assert!((rec.t - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn front_face_follows_the_winding() {
+        let a = Point3::new(0.0, 0.0, 0.0);
+        let b = Point3::new(1.0, 0.0, 0.0);
+        let c = Point3::new(0.0, 1.0, 0.0);
+        let ray = Ray::new(Point3::new(0.25, 0.25, 1.0), Vec3::z(-1.0), 0.0);
+
+        // counter-clockwise seen from the ray, the normal faces it
+        let counter_clockwise = triangle([a, b, c]);
+        let rec = counter_clockwise.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!(rec.front_face);
+        assert!((rec.normal - Vec3::z(1.0)).length() < 1e-12);
+
+        let clockwise = triangle([a, c, b]);
+        let rec = clockwise.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!(!rec.front_face);
+        assert!((rec.normal - Vec3::z(1.0)).length() < 1e-12);
+    }
+}
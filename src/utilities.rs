@@ -1,18 +1,14 @@
+use std::cell::RefCell;
 use std::fs::OpenOptions;
 use std::io::Write;
 
-use image::{Rgb, RgbImage};
-use rand::Rng;
+use image::{GrayImage, Luma, Rgb, RgbImage};
 
+use crate::rng::Pcg32;
 use crate::vec3::Color;
 
-pub fn save_image_as_ppm(
-    filepath: &String,
-    buffer: &Vec<Color>,
-    samples_per_pixel: u32,
-    image_width: u32,
-    image_height: u32,
-) {
+// The buffer holds the mean of the samples of every pixel
+pub fn save_image_as_ppm(filepath: &str, buffer: &[Color], image_width: u32, image_height: u32) {
     fn clamp_color(color: f64) -> i32 {
         (256.0 * clamp(color, 0.0, 0.999)) as i32
     }
@@ -20,18 +16,16 @@ pub fn save_image_as_ppm(
     let mut file = OpenOptions::new()
         .write(true)
         .create(true)
+        .truncate(true)
         .open(filepath)
         .unwrap();
 
     let mut ppm_image = format!("P3\n{image_width} {image_height}\n255\n");
 
-    let scale = 1.0 / (samples_per_pixel as f64);
-
     for pixel_color in buffer {
-        let scaled_color = *pixel_color * scale;
-        let r = scaled_color.x.sqrt();
-        let g = scaled_color.y.sqrt();
-        let b = scaled_color.z.sqrt();
+        let r = pixel_color.x.sqrt();
+        let g = pixel_color.y.sqrt();
+        let b = pixel_color.z.sqrt();
         let formatted_color =
             format!("{} {} {}\n", clamp_color(r), clamp_color(g), clamp_color(b),);
         ppm_image.push_str(&formatted_color);
@@ -39,26 +33,18 @@ pub fn save_image_as_ppm(
     file.write_all(ppm_image.as_bytes()).unwrap();
 }
 
-pub fn save_image_as_png(
-    filename: &str,
-    buffer: &[Color],
-    samples_per_pixel: u32,
-    image_width: u32,
-    image_height: u32,
-) {
+pub fn save_image_as_png(filename: &str, buffer: &[Color], image_width: u32, image_height: u32) {
     fn clamp_color(color: f64) -> u8 {
         (256.0 * clamp(color, 0.0, 0.999)) as u8
     }
 
     let mut rgb_image = RgbImage::new(image_width, image_height);
 
-    let scale = 1.0 / (samples_per_pixel as f64);
-
     for (i, (_, _, pixel)) in rgb_image.enumerate_pixels_mut().enumerate() {
-        let scaled_color = buffer[i] * scale;
-        let r = scaled_color.x.sqrt();
-        let g = scaled_color.y.sqrt();
-        let b = scaled_color.z.sqrt();
+        let color = buffer[i];
+        let r = color.x.sqrt();
+        let g = color.y.sqrt();
+        let b = color.z.sqrt();
 
         *pixel = Rgb([clamp_color(r), clamp_color(g), clamp_color(b)]);
     }
@@ -66,12 +52,45 @@ pub fn save_image_as_png(
     rgb_image.save(filename).expect("File write error");
 }
 
+// Grey levels proportional to the number of samples, white for max_samples
+pub fn save_sample_map(
+    filename: &str,
+    counts: &[u32],
+    max_samples: u32,
+    image_width: u32,
+    image_height: u32,
+) {
+    let mut gray_image = GrayImage::new(image_width, image_height);
+
+    for (i, (_, _, pixel)) in gray_image.enumerate_pixels_mut().enumerate() {
+        let fraction = counts[i] as f64 / max_samples as f64;
+        *pixel = Luma([(255.0 * fraction.min(1.0)).round() as u8]);
+    }
+
+    gray_image.save(filename).expect("File write error");
+}
+
+thread_local! {
+    static RNG: RefCell<Pcg32> = RefCell::new(Pcg32::new(0, 0));
+}
+
+// Restarts the generator of the calling thread
+pub fn seed_random(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = Pcg32::new(seed, 0));
+}
+
+// Switches the calling thread to the sequence of one sample of one pixel. Whichever
+// thread renders the sample, it draws the same numbers.
+pub fn seed_sample(seed: u64, pixel: u64, sample: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = Pcg32::for_sample(seed, pixel, sample));
+}
+
 pub fn random_double() -> f64 {
-    rand::thread_rng().gen_range(0.0..1.0)
+    RNG.with(|rng| rng.borrow_mut().next_double())
 }
 
 pub fn random_double_from_range(min: f64, max: f64) -> f64 {
-    rand::thread_rng().gen_range(min..max)
+    min + (max - min) * random_double()
 }
 
 pub fn clamp(x: f64, min: f64, max: f64) -> f64 {
@@ -83,3 +102,47 @@ pub fn clamp(x: f64, min: f64, max: f64) -> f64 {
     }
     x
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draws(seed: u64, pixel: u64, sample: u64) -> Vec<f64> {
+        seed_sample(seed, pixel, sample);
+        (0..16).map(|_| random_double()).collect()
+    }
+
+    #[test]
+    fn sample_draws_do_not_depend_on_thread_or_order() {
+        let expected = draws(3, 1234, 7);
+
+        // Other samples in between and another thread give the same numbers
+        draws(3, 1235, 0);
+        random_double();
+        assert_eq!(draws(3, 1234, 7), expected);
+
+        let other_thread = std::thread::spawn(|| {
+            seed_random(99);
+            draws(3, 1234, 7)
+        });
+        assert_eq!(other_thread.join().unwrap(), expected);
+    }
+
+    #[test]
+    fn neighbouring_pixels_and_samples_get_different_streams() {
+        let base = draws(3, 1234, 7);
+        for (seed, pixel, sample) in [
+            (3, 1235, 7),
+            (3, 1233, 7),
+            (3, 1234, 8),
+            (3, 1234, 6),
+            (4, 1234, 7),
+        ] {
+            let other = draws(seed, pixel, sample);
+            let equal = base.iter().zip(&other).filter(|(a, b)| a == b).count();
+            assert_eq!(equal, 0, "seed {seed}, pixel {pixel}, sample {sample}");
+        }
+        // Swapping the pixel and sample index doesn't give the same stream either
+        assert_ne!(draws(3, 5, 9), draws(3, 9, 5));
+    }
+}
@@ -1,6 +1,6 @@
 use std::fmt::{Display, Formatter};
 use std::iter::Sum;
-use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
+use std::ops::{Add, AddAssign, Div, Index, Mul, MulAssign, Neg, Sub, SubAssign};
 
 use crate::utilities::{random_double, random_double_from_range};
 
@@ -51,6 +51,23 @@ impl Vec3 {
         Vec3 { x: 0.0, y: 0.0, z }
     }
 
+    // component-wise minimum and maximum, used for bounding boxes
+    pub fn min(u: Vec3, v: Vec3) -> Vec3 {
+        Vec3 {
+            x: u.x.min(v.x),
+            y: u.y.min(v.y),
+            z: u.z.min(v.z),
+        }
+    }
+
+    pub fn max(u: Vec3, v: Vec3) -> Vec3 {
+        Vec3 {
+            x: u.x.max(v.x),
+            y: u.y.max(v.y),
+            z: u.z.max(v.z),
+        }
+    }
+
     pub fn length(&self) -> f64 {
         self.length_squared().sqrt()
     }
@@ -90,11 +107,6 @@ impl Vec3 {
         }
     }
 
-    // True Lambertian diffuse
-    pub fn random_unit_vector() -> Vec3 {
-        Vec3::random_in_unit_sphere().unit_vector()
-    }
-
     pub fn random_from_range(min: f64, max: f64) -> Vec3 {
         Vec3 {
             x: random_double_from_range(min, max),
@@ -103,22 +115,18 @@ impl Vec3 {
         }
     }
 
-    pub fn random_in_unit_sphere() -> Vec3 {
-        loop {
-            let p = Vec3::random_from_range(-1.0, 1.0);
-            if p.length_squared() < 1.0 {
-                return p;
-            }
-        }
+    pub fn max_component(self) -> f64 {
+        self.x.max(self.y).max(self.z)
     }
 
-    // Diffuse method - Uniform scatter direction with no dependence on the normal
-    pub fn random_in_hemisphere(normal: &Vec3) -> Vec3 {
-        let in_unit_sphere = Vec3::random_in_unit_sphere();
-        if dot(in_unit_sphere, *normal) > 0.0 {
-            in_unit_sphere
+    // Index of the largest component, ties go to the later one
+    pub fn max_dimension(self) -> usize {
+        if self.x > self.y && self.x > self.z {
+            0
+        } else if self.y > self.z {
+            1
         } else {
-            -in_unit_sphere
+            2
         }
     }
 
@@ -128,11 +136,6 @@ impl Vec3 {
         self.x.abs() < threshold && self.y.abs() < threshold && self.z.abs() < threshold
     }
 
-    // Reflections for smooth metals
-    pub fn reflect(v: &Vec3, normal: &Vec3) -> Vec3 {
-        *v - 2.0 * dot(*v, *normal) * *normal
-    }
-
     pub fn refract(uv: &Vec3, n: &Vec3, refr_indexes_proportion: f64) -> Vec3 {
         let cos_theta = 1.0_f64.min(dot(-*uv, *n));
         let r_out_perp = refr_indexes_proportion * (*uv + cos_theta * *n);
@@ -140,18 +143,14 @@ impl Vec3 {
 
         r_out_perp + r_out_parallel
     }
+}
 
-    pub fn random_in_unit_disk() -> Vec3 {
-        loop {
-            let p = Vec3 {
-                x: random_double_from_range(-1.0, 1.0),
-                y: random_double_from_range(-1.0, 1.0),
-                z: 0.0,
-            };
-            if p.length_squared() >= 1.0 {
-                continue;
-            }
-            return p;
+impl From<[f64; 3]> for Vec3 {
+    fn from(v: [f64; 3]) -> Self {
+        Vec3 {
+            x: v[0],
+            y: v[1],
+            z: v[2],
         }
     }
 }
@@ -164,6 +163,20 @@ impl Display for Vec3 {
     }
 }
 
+impl Index<usize> for Vec3 {
+    type Output = f64;
+
+    #[inline]
+    fn index(&self, axis: usize) -> &Self::Output {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 index out of range: {axis}"),
+        }
+    }
+}
+
 impl Add for Vec3 {
     type Output = Self;
 
@@ -265,6 +278,19 @@ impl MulAssign<f64> for Vec3 {
     }
 }
 
+impl Div for Vec3 {
+    type Output = Self;
+
+    #[inline]
+    fn div(self, rhs: Vec3) -> Self::Output {
+        Self {
+            x: self.x / rhs.x,
+            y: self.y / rhs.y,
+            z: self.z / rhs.z,
+        }
+    }
+}
+
 impl Div<f64> for Vec3 {
     type Output = Self;
 
@@ -277,3 +303,78 @@ impl Div<f64> for Vec3 {
 pub fn dot(u: Vec3, v: Vec3) -> f64 {
     u.x * v.x + u.y * v.y + u.z * v.z
 }
+
+// Orthonormal basis, local coordinates (x, y, z) map to u, v and w
+#[derive(Copy, Clone)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    // Basis with w along the given direction (Duff et al., "Building an
+    // Orthonormal Basis, Revisited"), continuous everywhere except around -z
+    pub fn from_w(direction: Vec3) -> Self {
+        let w = direction.unit_vector();
+        let sign = 1.0_f64.copysign(w.z);
+        let a = -1.0 / (sign + w.z);
+        let b = w.x * w.y * a;
+        Onb {
+            u: Vec3::new(1.0 + sign * w.x * w.x * a, sign * b, -sign * w.x),
+            v: Vec3::new(b, sign + w.y * w.y * a, -w.y),
+            w,
+        }
+    }
+
+    pub fn to_local(self, p: Vec3) -> Vec3 {
+        Vec3::new(dot(p, self.u), dot(p, self.v), dot(p, self.w))
+    }
+
+    pub fn to_world(self, p: Vec3) -> Vec3 {
+        p.x * self.u + p.y * self.v + p.z * self.w
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directions() -> Vec<Vec3> {
+        vec![
+            Vec3::z(1.0),
+            Vec3::z(-1.0),
+            Vec3::new(1e-9, -1e-9, 1.0),
+            Vec3::new(1e-9, 1e-9, -1.0),
+            Vec3::new(-1e-4, 2e-4, -1.0),
+            Vec3::new(0.3, -0.5, 0.2),
+            Vec3::x(-2.0),
+        ]
+    }
+
+    #[test]
+    fn basis_is_orthonormal() {
+        for direction in directions() {
+            let Onb { u, v, w } = Onb::from_w(direction);
+            for (a, b) in [(u, v), (v, w), (w, u)] {
+                assert!(dot(a, b).abs() < 1e-12, "{}", dot(a, b));
+            }
+            for axis in [u, v, w] {
+                assert!((axis.length() - 1.0).abs() < 1e-12, "{}", axis.length());
+            }
+            // right handed, w along the direction
+            assert!((Vec3::cross(u, v) - w).length() < 1e-12);
+            assert!((w - direction.unit_vector()).length() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn to_world_undoes_to_local() {
+        let p = Vec3::new(0.7, -1.3, 2.1);
+        for direction in directions() {
+            let frame = Onb::from_w(direction);
+            assert!((frame.to_world(frame.to_local(p)) - p).length() < 1e-12);
+            assert!((frame.to_local(frame.to_world(p)) - p).length() < 1e-12);
+        }
+    }
+}
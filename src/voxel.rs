@@ -0,0 +1,252 @@
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use crate::Point3;
+
+#[derive(Debug)]
+pub enum VoxelError {
+    Io {
+        file: PathBuf,
+        error: std::io::Error,
+    },
+    Parse {
+        file: PathBuf,
+        message: String,
+    },
+}
+
+impl Display for VoxelError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VoxelError::Io { file, error } => write!(f, "{}: {error}", file.display()),
+            VoxelError::Parse { file, message } => write!(f, "{}: {message}", file.display()),
+        }
+    }
+}
+
+impl std::error::Error for VoxelError {}
+
+// Density values on a regular grid spanning the unit cube, x varies fastest
+pub struct VoxelGrid {
+    resolution: [usize; 3],
+    values: Vec<f64>,
+    max_value: f64,
+}
+
+impl VoxelGrid {
+    pub fn new(resolution: [usize; 3], values: Vec<f64>) -> Result<Self, String> {
+        if resolution.contains(&0) {
+            return Err(String::from(
+                "resolution must be at least 1 in every direction",
+            ));
+        }
+        let expected = resolution
+            .iter()
+            .try_fold(1usize, |count, &size| count.checked_mul(size))
+            .ok_or_else(|| {
+                format!(
+                    "grid resolution {}x{}x{} is too large",
+                    resolution[0], resolution[1], resolution[2]
+                )
+            })?;
+        if values.len() != expected {
+            return Err(format!(
+                "expected {expected} values for a {}x{}x{} grid, found {}",
+                resolution[0],
+                resolution[1],
+                resolution[2],
+                values.len()
+            ));
+        }
+        if let Some(value) = values
+            .iter()
+            .find(|value| !value.is_finite() || **value < 0.0)
+        {
+            return Err(format!(
+                "densities must be finite and positive or zero, found {value}"
+            ));
+        }
+
+        let max_value = values.iter().copied().fold(0.0, f64::max);
+        Ok(VoxelGrid {
+            resolution,
+            values,
+            max_value,
+        })
+    }
+
+    // Files ending in .raw hold little endian 32 bit floats and need the resolution
+    // given separately. Text files start with the resolution "nx ny nz" followed by
+    // the values, # starts a comment.
+    pub fn load(path: &Path, resolution: Option<[usize; 3]>) -> Result<Self, VoxelError> {
+        let bytes = std::fs::read(path).map_err(|error| VoxelError::Io {
+            file: path.to_path_buf(),
+            error,
+        })?;
+        VoxelGrid::parse(&bytes, path, resolution)
+    }
+
+    // The path only picks the format and names the file in errors
+    pub fn parse(
+        bytes: &[u8],
+        path: &Path,
+        resolution: Option<[usize; 3]>,
+    ) -> Result<Self, VoxelError> {
+        let parse_error = |message| VoxelError::Parse {
+            file: path.to_path_buf(),
+            message,
+        };
+
+        let raw = path.extension().is_some_and(|extension| extension == "raw");
+        let (resolution, values) = if raw {
+            let resolution = resolution.ok_or_else(|| {
+                parse_error(String::from("raw voxel files need the grid resolution"))
+            })?;
+            if !bytes.len().is_multiple_of(4) {
+                return Err(parse_error(format!(
+                    "file size of {} bytes is not a multiple of 4",
+                    bytes.len()
+                )));
+            }
+            let values = bytes
+                .chunks_exact(4)
+                .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()) as f64)
+                .collect();
+            (resolution, values)
+        } else {
+            let text = std::str::from_utf8(bytes)
+                .map_err(|_| parse_error(String::from("voxel file is not valid UTF-8")))?;
+            let mut numbers = text
+                .lines()
+                .map(|line| line.split('#').next().unwrap_or_default())
+                .flat_map(str::split_whitespace);
+
+            let mut header = [0; 3];
+            for size in header.iter_mut() {
+                let number = numbers
+                    .next()
+                    .ok_or_else(|| parse_error(String::from("missing grid resolution")))?;
+                *size = number
+                    .parse()
+                    .map_err(|_| parse_error(format!("'{number}' is not a grid size")))?;
+            }
+            if resolution.is_some_and(|resolution| resolution != header) {
+                return Err(parse_error(String::from(
+                    "resolution in the file differs from the given one",
+                )));
+            }
+
+            let values = numbers
+                .map(|number| {
+                    number
+                        .parse()
+                        .map_err(|_| parse_error(format!("'{number}' is not a number")))
+                })
+                .collect::<Result<Vec<f64>, VoxelError>>()?;
+            (header, values)
+        };
+
+        VoxelGrid::new(resolution, values).map_err(parse_error)
+    }
+
+    pub fn max_value(&self) -> f64 {
+        self.max_value
+    }
+
+    // Trilinear interpolation between voxel centers, zero outside of the unit cube
+    pub fn value(&self, p: &Point3) -> f64 {
+        let coordinates = [p.x, p.y, p.z];
+        if coordinates.iter().any(|c| !(0.0..=1.0).contains(c)) {
+            return 0.0;
+        }
+
+        let mut lower = [0; 3];
+        let mut upper = [0; 3];
+        let mut fraction = [0.0; 3];
+        for axis in 0..3 {
+            let size = self.resolution[axis];
+            let x = (coordinates[axis] * size as f64 - 0.5).clamp(0.0, (size - 1) as f64);
+            lower[axis] = x.floor() as usize;
+            upper[axis] = (lower[axis] + 1).min(size - 1);
+            fraction[axis] = x - x.floor();
+        }
+
+        let mut value = 0.0;
+        for corner in 0..8 {
+            let mut index = [0; 3];
+            let mut weight = 1.0;
+            for axis in 0..3 {
+                if corner & (1 << axis) == 0 {
+                    index[axis] = lower[axis];
+                    weight *= 1.0 - fraction[axis];
+                } else {
+                    index[axis] = upper[axis];
+                    weight *= fraction[axis];
+                }
+            }
+            value += weight * self.voxel(index);
+        }
+        value
+    }
+
+    fn voxel(&self, [x, y, z]: [usize; 3]) -> f64 {
+        let [nx, ny, _] = self.resolution;
+        self.values[(z * ny + y) * nx + x]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(text: &[u8], file: &str, resolution: Option<[usize; 3]>) -> String {
+        match VoxelGrid::parse(text, Path::new(file), resolution) {
+            Err(VoxelError::Parse { message, .. }) => message,
+            Err(error) => panic!("unexpected error {error}"),
+            Ok(_) => panic!("expected an error"),
+        }
+    }
+
+    #[test]
+    fn text_grid_loads() {
+        let grid =
+            VoxelGrid::parse(b"2 1 1 # header\n0.0 4.0\n", Path::new("grid.txt"), None).unwrap();
+        assert_eq!(grid.max_value(), 4.0);
+        assert!((grid.value(&Point3::new(0.5, 0.5, 0.5)) - 2.0).abs() < 1e-12);
+        assert_eq!(grid.value(&Point3::new(1.5, 0.5, 0.5)), 0.0);
+    }
+
+    #[test]
+    fn raw_grid_loads() {
+        let bytes: Vec<u8> = [1.0f32, 3.0]
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        let grid = VoxelGrid::parse(&bytes, Path::new("grid.raw"), Some([1, 2, 1])).unwrap();
+        assert_eq!(grid.max_value(), 3.0);
+    }
+
+    #[test]
+    fn huge_resolution_is_rejected() {
+        let message = parse_error(b"99999999999 99999999999 9\n", "grid.txt", None);
+        assert!(message.contains("too large"), "{message}");
+    }
+
+    #[test]
+    fn raw_file_needs_a_resolution() {
+        let message = parse_error(&[0; 8], "grid.raw", None);
+        assert!(message.contains("need the grid resolution"), "{message}");
+    }
+
+    #[test]
+    fn file_resolution_must_match_the_given_one() {
+        let message = parse_error(b"1 1 1\n0.5\n", "grid.txt", Some([2, 1, 1]));
+        assert!(message.contains("differs"), "{message}");
+    }
+
+    #[test]
+    fn value_count_must_match_the_resolution() {
+        let message = parse_error(b"2 2 1\n0.5 0.5 0.5\n", "grid.txt", None);
+        assert!(message.contains("expected 4 values"), "{message}");
+    }
+}